@@ -0,0 +1,155 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::client_error::ClientError;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::retry::{self, RetryPolicy};
+use crate::transaction::{self, NextBucket, Payout};
+use crate::scanner;
+
+pub struct KeeperConfig {
+    pub usdc_mint: Pubkey,
+    pub retry: RetryPolicy,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub charged: usize,
+    pub finalized: usize,
+    pub released: usize,
+    pub not_due: usize,
+    pub failed: usize,
+}
+
+pub struct Keeper {
+    rpc: RpcClient,
+    payer: Keypair,
+    config: KeeperConfig,
+}
+
+impl Keeper {
+    pub fn new(rpc: RpcClient, payer: Keypair, config: KeeperConfig) -> Self {
+        Keeper { rpc, payer, config }
+    }
+
+    /// Scan once and charge everything due at the current wall-clock time
+    pub async fn run_once(&self) -> Result<RunSummary> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        self.charge_due(now).await
+    }
+
+    /// Charge every active subscription due at `now`, then release held
+    /// payments whose dispute window has closed. Failures are logged and
+    /// counted so one bad subscription doesn't stall the rest of the run.
+    pub async fn charge_due(&self, now: i64) -> Result<RunSummary> {
+        let platform_config = scanner::platform_config(&self.rpc).await?;
+        let reserve = scanner::lending_reserve(&self.rpc, &platform_config).await?;
+        let subscriptions = scanner::active_subscriptions(&self.rpc).await?;
+        let mut summary = RunSummary::default();
+
+        for (key, subscription) in subscriptions {
+            if !scanner::is_due(&subscription, now) {
+                summary.not_due += 1;
+                continue;
+            }
+
+            // Canceled at period end: the final crank refunds instead of charging
+            if subscription.cancel_at.is_some() {
+                if subscription.has_illiquid_positions() {
+                    // Unwinding needs a pending refund record, which the user pays for
+                    log::info!("{key} has staked, pooled or lent escrow; leaving finalization to the user");
+                    summary.not_due += 1;
+                    continue;
+                }
+
+                let instructions = [transaction::finalize_instruction(&self.payer.pubkey(), &key, &subscription)];
+                match self.send(&instructions).await {
+                    Ok(signature) => {
+                        log::info!("finalized {key} in {signature}");
+                        summary.finalized += 1;
+                    }
+                    Err(error) => {
+                        log::error!("failed to finalize {key}: {error}");
+                        summary.failed += 1;
+                    }
+                }
+                continue;
+            }
+
+            let next_due = subscription.next_payment_due + subscription.interval_seconds;
+            let (page, needs_open) = scanner::open_page_for(&self.rpc, next_due).await?;
+            let recipient = &subscription.recipient_token_account;
+            // Held charges are split when the merchant claims them, not here
+            let payout = if platform_config.settlement_delay > 0 {
+                Payout {
+                    payees: Vec::new(),
+                    held: true,
+                    settlement_needs_open: !scanner::settlement_exists(&self.rpc, recipient).await?,
+                }
+            } else {
+                Payout {
+                    payees: scanner::payees(&self.rpc, recipient).await?,
+                    held: false,
+                    settlement_needs_open: false,
+                }
+            };
+            let instructions = transaction::charge_instructions(
+                &self.payer.pubkey(),
+                &key,
+                &subscription,
+                &platform_config,
+                &self.config.usdc_mint,
+                &NextBucket { page, needs_open },
+                reserve.as_ref(),
+                &payout,
+            );
+
+            match self.send(&instructions).await {
+                Ok(signature) => {
+                    log::info!("charged {key} in {signature}");
+                    summary.charged += 1;
+                }
+                Err(error) => {
+                    log::error!("failed to charge {key}: {error}");
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        // Held charges whose dispute window has closed; releasing them also
+        // returns the rent the keeper paid when charging
+        for (key, held_payment) in scanner::releasable_held_payments(&self.rpc, now).await? {
+            match self.send(&[transaction::release_instruction(&key, &held_payment)]).await {
+                Ok(signature) => {
+                    log::info!("released {key} in {signature}");
+                    summary.released += 1;
+                }
+                Err(error) => {
+                    log::error!("failed to release {key}: {error}");
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn send(&self, instructions: &[Instruction]) -> Result<Signature, ClientError> {
+        retry::with_backoff(&self.config.retry, || async {
+            let blockhash = self.rpc.get_latest_blockhash().await?;
+            let tx = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&self.payer.pubkey()),
+                &[&self.payer],
+                blockhash,
+            );
+            self.rpc.send_and_confirm_transaction(&tx).await
+        })
+        .await
+    }
+}
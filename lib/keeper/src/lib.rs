@@ -0,0 +1,7 @@
+pub mod pda;
+pub mod scanner;
+pub mod transaction;
+pub mod retry;
+pub mod keeper;
+
+pub use keeper::{Keeper, KeeperConfig, RunSummary};
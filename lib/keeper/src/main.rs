@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{Context, Result};
+use clap::Parser;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::read_keypair_file;
+
+use subscription_keeper::retry::RetryPolicy;
+use subscription_keeper::{Keeper, KeeperConfig};
+
+/// Charges due escrow subscriptions and collects keeper rewards
+#[derive(Parser)]
+struct Args {
+    /// JSON RPC endpoint
+    #[arg(long, env = "KEEPER_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    rpc_url: String,
+
+    /// Keypair that signs and pays for charge transactions
+    #[arg(long, env = "KEEPER_KEYPAIR")]
+    keypair: String,
+
+    /// Mint of the stablecoin subscriptions are billed in
+    #[arg(long, env = "KEEPER_USDC_MINT")]
+    usdc_mint: Pubkey,
+
+    /// Seconds between scans
+    #[arg(long, env = "KEEPER_POLL_INTERVAL", default_value_t = 60)]
+    poll_interval: u64,
+
+    /// Attempts per charge before giving up until the next scan
+    #[arg(long, env = "KEEPER_MAX_ATTEMPTS", default_value_t = 5)]
+    max_attempts: u32,
+
+    /// Run a single scan and exit
+    #[arg(long)]
+    once: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let args = Args::parse();
+
+    let payer = read_keypair_file(&args.keypair)
+        .map_err(|error| anyhow::anyhow!("{error}"))
+        .with_context(|| format!("reading keypair {}", args.keypair))?;
+    let rpc = RpcClient::new_with_commitment(args.rpc_url, CommitmentConfig::confirmed());
+
+    let keeper = Keeper::new(
+        rpc,
+        payer,
+        KeeperConfig {
+            usdc_mint: args.usdc_mint,
+            retry: RetryPolicy {
+                max_attempts: args.max_attempts,
+                ..RetryPolicy::default()
+            },
+        },
+    );
+
+    loop {
+        match keeper.run_once().await {
+            Ok(summary) => log::info!("{summary:?}"),
+            Err(error) => log::error!("scan failed: {error:#}"),
+        }
+
+        if args.once {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(args.poll_interval)).await;
+    }
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::Pubkey;
+use subscription_manager::state::ScheduleBucket;
+
+/// Subscription PDA, which is also the escrow signer
+pub fn subscription(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"subscription", user.as_ref()], &subscription_manager::ID).0
+}
+
+pub fn platform_config() -> Pubkey {
+    Pubkey::find_program_address(&[b"platform-config"], &subscription_manager::ID).0
+}
+
+/// Schedule page for the day containing `timestamp`
+pub fn schedule_bucket(timestamp: i64, page: u16) -> Pubkey {
+    let day = ScheduleBucket::day_of(timestamp);
+    Pubkey::find_program_address(
+        &[b"schedule", day.to_le_bytes().as_ref(), page.to_le_bytes().as_ref()],
+        &subscription_manager::ID,
+    )
+    .0
+}
+
+/// Stats account of a referrer wallet
+pub fn referrer(wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"referrer", wallet.as_ref()], &subscription_manager::ID).0
+}
+
+/// Payout split of a merchant payout account, which may not exist
+pub fn payout_split(recipient_token_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"payout-split", recipient_token_account.as_ref()], &subscription_manager::ID).0
+}
+
+/// Settlement holding a merchant's charges while the platform holds settlements
+pub fn settlement(recipient_token_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"settlement", recipient_token_account.as_ref()], &subscription_manager::ID).0
+}
+
+pub fn settlement_vault(recipient_token_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"settlement-vault", recipient_token_account.as_ref()], &subscription_manager::ID).0
+}
+
+/// Record of one charge held in a merchant's settlement
+pub fn held_payment(recipient_token_account: &Pubkey, subscription: &Pubkey, due_at: i64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"held", recipient_token_account.as_ref(), subscription.as_ref(), due_at.to_le_bytes().as_ref()],
+        &subscription_manager::ID,
+    )
+    .0
+}
+
+/// Vault holding a subscription's lending reserve collateral
+pub fn collateral_vault(subscription: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"collateral", subscription.as_ref()], &subscription_manager::ID).0
+}
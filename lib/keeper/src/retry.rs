@@ -0,0 +1,60 @@
+use std::future::Future;
+use std::time::Duration;
+
+use solana_client::client_error::ClientError;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (1-based), doubling each time
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Program errors (not due, underfunded, already charged by another keeper)
+/// won't change on retry; network and blockhash failures might
+pub fn is_retryable(error: &ClientError) -> bool {
+    !matches!(
+        error.get_transaction_error(),
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(_)))
+    )
+}
+
+/// Run `operation` until it succeeds, fails permanently, or runs out of attempts
+pub async fn with_backoff<T, F, Fut>(policy: &RetryPolicy, mut operation: F) -> Result<T, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) if attempt < policy.max_attempts && is_retryable(&error) => {
+                log::warn!("attempt {attempt} failed, retrying: {error}");
+                tokio::time::sleep(policy.delay(attempt)).await;
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
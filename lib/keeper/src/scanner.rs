@@ -0,0 +1,144 @@
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use subscription_manager::lending::{self, ReserveState};
+use subscription_manager::state::{HeldPayment, PayoutSplit, PlatformConfig, ScheduleBucket, Subscription};
+
+use crate::pda;
+
+// Account discriminator + user + escrow_token_account + monthly_amount
+const IS_ACTIVE_OFFSET: usize = 8 + 32 + 32 + 8;
+
+/// Fetch every active subscription, filtering server-side on the account
+/// discriminator, size and `is_active` flag so the RPC does the heavy lifting
+pub async fn active_subscriptions(rpc: &RpcClient) -> Result<Vec<(Pubkey, Subscription)>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize((8 + Subscription::LEN) as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &Subscription::discriminator())),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(IS_ACTIVE_OFFSET, &[1])),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = rpc
+        .get_program_accounts_with_config(&subscription_manager::ID, config)
+        .await?;
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(key, account)| {
+            Subscription::try_deserialize(&mut account.data.as_slice())
+                .ok()
+                .map(|subscription| (key, subscription))
+        })
+        .collect())
+}
+
+/// Fetch every held payment that can be released at `now`: undisputed and
+/// past its release time
+pub async fn releasable_held_payments(rpc: &RpcClient, now: i64) -> Result<Vec<(Pubkey, HeldPayment)>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize((8 + HeldPayment::LEN) as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &HeldPayment::discriminator())),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = rpc
+        .get_program_accounts_with_config(&subscription_manager::ID, config)
+        .await?;
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(key, account)| {
+            HeldPayment::try_deserialize(&mut account.data.as_slice())
+                .ok()
+                .map(|held_payment| (key, held_payment))
+        })
+        .filter(|(_, held_payment)| !held_payment.disputed && now >= held_payment.release_at)
+        .collect())
+}
+
+/// When the next charge may be submitted. A never-charged subscription is
+/// due at creation; after that the program advances `next_payment_due` by
+/// `interval_seconds` per charged period rather than from
+/// `last_payment_timestamp`, so late charges don't push the schedule back.
+/// Submitting before this fails on-chain with `PaymentNotDue`.
+pub fn due_at(subscription: &Subscription) -> i64 {
+    subscription.next_payment_due
+}
+
+/// Streams are paid out by the merchant and never charged by keepers.
+pub fn is_due(subscription: &Subscription, now: i64) -> bool {
+    subscription.is_active && subscription.stream_rate == 0 && now >= due_at(subscription)
+}
+
+pub async fn platform_config(rpc: &RpcClient) -> Result<PlatformConfig> {
+    let data = rpc.get_account_data(&pda::platform_config()).await?;
+    Ok(PlatformConfig::try_deserialize(&mut data.as_slice())?)
+}
+
+/// The configured lending reserve, if the platform has one set
+pub async fn lending_reserve(rpc: &RpcClient, platform_config: &PlatformConfig) -> Result<Option<ReserveState>> {
+    if platform_config.lending_reserve == Pubkey::default() {
+        return Ok(None);
+    }
+    let data = rpc.get_account_data(&platform_config.lending_reserve).await?;
+    Ok(Some(lending::parse_reserve(&data)?))
+}
+
+/// Payee token accounts a charge to `recipient_token_account` is split
+/// between, in order; empty when the merchant has no payout split
+pub async fn payees(rpc: &RpcClient, recipient_token_account: &Pubkey) -> Result<Vec<Pubkey>> {
+    let account = rpc
+        .get_account_with_commitment(&pda::payout_split(recipient_token_account), rpc.commitment())
+        .await?
+        .value;
+    let Some(account) = account else {
+        return Ok(Vec::new());
+    };
+    let split = PayoutSplit::try_deserialize(&mut account.data.as_slice())?;
+    Ok(split.payees.iter().map(|payee| payee.token_account).collect())
+}
+
+/// Whether a merchant's settlement has been opened yet
+pub async fn settlement_exists(rpc: &RpcClient, recipient_token_account: &Pubkey) -> Result<bool> {
+    let account = rpc
+        .get_account_with_commitment(&pda::settlement(recipient_token_account), rpc.commitment())
+        .await?
+        .value;
+    Ok(account.is_some())
+}
+
+/// Find a page of the schedule bucket for `timestamp`'s day with room for one
+/// more subscription. Returns the page and whether it still needs opening.
+pub async fn open_page_for(rpc: &RpcClient, timestamp: i64) -> Result<(u16, bool)> {
+    let mut page = 0u16;
+    loop {
+        let key = pda::schedule_bucket(timestamp, page);
+        match rpc.get_account_data(&key).await {
+            Ok(data) => {
+                let bucket = ScheduleBucket::try_deserialize(&mut data.as_slice())?;
+                if !bucket.is_full() {
+                    return Ok((page, false));
+                }
+            }
+            Err(_) => return Ok((page, true)),
+        }
+        page += 1;
+    }
+}
@@ -0,0 +1,187 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use spl_associated_token_account::get_associated_token_address;
+use subscription_manager::lending::{self, ReserveState};
+use subscription_manager::state::{HeldPayment, PlatformConfig, ScheduleBucket, Subscription};
+use subscription_manager::{accounts, instruction};
+
+use crate::pda;
+
+/// Where the charged subscription is refiled in the payment schedule
+pub struct NextBucket {
+    pub page: u16,
+    pub needs_open: bool,
+}
+
+/// Where the merchant's part of the charge goes
+pub struct Payout {
+    // Payee token accounts of the merchant's payout split, in order
+    pub payees: Vec<Pubkey>,
+
+    // Whether the platform holds settlements, and if so whether the
+    // merchant's settlement still needs opening
+    pub held: bool,
+    pub settlement_needs_open: bool,
+}
+
+/// Build the instructions that charge one due subscription: opening the next
+/// schedule page and the merchant's settlement if nobody has yet, pulling
+/// lent escrow back out of the lending reserve if any is out, then
+/// `process_payment` itself, carrying the merchant's payout split payees
+#[allow(clippy::too_many_arguments)]
+pub fn charge_instructions(
+    keeper: &Pubkey,
+    subscription_key: &Pubkey,
+    subscription: &Subscription,
+    platform_config: &PlatformConfig,
+    usdc_mint: &Pubkey,
+    next_bucket: &NextBucket,
+    reserve: Option<&ReserveState>,
+    payout: &Payout,
+) -> Vec<Instruction> {
+    let next_due = subscription.next_payment_due + subscription.interval_seconds;
+    let next_bucket_key = pda::schedule_bucket(next_due, next_bucket.page);
+    let recipient = &subscription.recipient_token_account;
+    let mut instructions = Vec::with_capacity(4);
+
+    if next_bucket.needs_open {
+        instructions.push(Instruction {
+            program_id: subscription_manager::ID,
+            accounts: accounts::OpenScheduleBucket {
+                schedule_bucket: next_bucket_key,
+                payer: *keeper,
+                system_program: solana_sdk::system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::OpenScheduleBucket {
+                day: ScheduleBucket::day_of(next_due),
+                page: next_bucket.page,
+            }
+            .data(),
+        });
+    }
+
+    if payout.settlement_needs_open {
+        instructions.push(Instruction {
+            program_id: subscription_manager::ID,
+            accounts: accounts::OpenSettlement {
+                settlement: pda::settlement(recipient),
+                settlement_vault: pda::settlement_vault(recipient),
+                recipient_token_account: *recipient,
+                usdc_mint: *usdc_mint,
+                payer: *keeper,
+                token_program: anchor_spl::token::ID,
+                system_program: solana_sdk::system_program::ID,
+                rent: solana_sdk::sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::OpenSettlement {}.data(),
+        });
+    }
+
+    // withdraw_lent_escrow is a no-op when the escrow already covers the charge
+    if let Some(reserve) = reserve.filter(|_| subscription.lent_collateral > 0) {
+        instructions.push(Instruction {
+            program_id: subscription_manager::ID,
+            accounts: accounts::WithdrawLentEscrow {
+                subscription: *subscription_key,
+                subscription_signer: *subscription_key,
+                platform_config: pda::platform_config(),
+                price_oracle: platform_config.price_oracle,
+                escrow_token_account: subscription.escrow_token_account,
+                lending_reserve: platform_config.lending_reserve,
+                reserve_liquidity_oracle: reserve.liquidity_oracle,
+                reserve_liquidity_supply: reserve.liquidity_supply,
+                reserve_collateral_mint: reserve.collateral_mint,
+                lending_market: reserve.lending_market,
+                lending_market_authority: lending::market_authority(
+                    &reserve.lending_market,
+                    &platform_config.lending_program,
+                ),
+                collateral_vault: pda::collateral_vault(subscription_key),
+                lending_program: platform_config.lending_program,
+                token_program: anchor_spl::token::ID,
+                clock: solana_sdk::sysvar::clock::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::WithdrawLentEscrow {}.data(),
+        });
+    }
+
+    let mut process_payment_accounts = accounts::ProcessPayment {
+        subscription: *subscription_key,
+        subscription_signer: *subscription_key,
+        user: subscription.user,
+        escrow_token_account: subscription.escrow_token_account,
+        recipient_token_account: subscription.recipient_token_account,
+        fee_wallet_token_account: get_associated_token_address(&platform_config.fee_wallet, usdc_mint),
+        platform_config: pda::platform_config(),
+        schedule_bucket: pda::schedule_bucket(subscription.next_payment_due, subscription.schedule_page),
+        next_schedule_bucket: next_bucket_key,
+        price_oracle: platform_config.price_oracle,
+        keeper: *keeper,
+        keeper_token_account: get_associated_token_address(keeper, usdc_mint),
+        usdc_mint: *usdc_mint,
+        plan: subscription.plan,
+        referrer: subscription.referrer.map(|wallet| pda::referrer(&wallet)),
+        referrer_token_account: subscription
+            .referrer
+            .map(|wallet| get_associated_token_address(&wallet, usdc_mint)),
+        payout_split: pda::payout_split(recipient),
+        settlement: payout.held.then(|| pda::settlement(recipient)),
+        settlement_vault: payout.held.then(|| pda::settlement_vault(recipient)),
+        held_payment: payout
+            .held
+            .then(|| pda::held_payment(recipient, subscription_key, subscription.next_payment_due)),
+        token_program: anchor_spl::token::ID,
+        system_program: solana_sdk::system_program::ID,
+    }
+    .to_account_metas(None);
+    process_payment_accounts.extend(payout.payees.iter().map(|payee| AccountMeta::new(*payee, false)));
+
+    instructions.push(Instruction {
+        program_id: subscription_manager::ID,
+        accounts: process_payment_accounts,
+        data: instruction::ProcessPayment {}.data(),
+    });
+
+    instructions
+}
+
+/// Release a held payment whose dispute window has closed, returning its rent
+/// to the keeper that charged it
+pub fn release_instruction(held_payment_key: &Pubkey, held_payment: &HeldPayment) -> Instruction {
+    Instruction {
+        program_id: subscription_manager::ID,
+        accounts: accounts::ReleaseHeldPayment {
+            settlement: pda::settlement(&held_payment.recipient_token_account),
+            held_payment: *held_payment_key,
+            payer: held_payment.payer,
+        }
+        .to_account_metas(None),
+        data: instruction::ReleaseHeldPayment {}.data(),
+    }
+}
+
+/// Refund a subscription canceled at period end once that period is over.
+/// Only valid when none of its escrow is staked, pooled or lent.
+pub fn finalize_instruction(keeper: &Pubkey, subscription_key: &Pubkey, subscription: &Subscription) -> Instruction {
+    Instruction {
+        program_id: subscription_manager::ID,
+        accounts: accounts::FinalizeCancellation {
+            subscription: *subscription_key,
+            subscription_signer: *subscription_key,
+            escrow_token_account: subscription.escrow_token_account,
+            destination_token_account: subscription.refund_destination.unwrap_or_default(),
+            recipient_token_account: subscription.recipient_token_account,
+            schedule_bucket: pda::schedule_bucket(subscription.next_payment_due, subscription.schedule_page),
+            pending_refund: None,
+            payer: *keeper,
+            token_program: anchor_spl::token::ID,
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::FinalizeCancellation {}.data(),
+    }
+}
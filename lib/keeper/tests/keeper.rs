@@ -0,0 +1,309 @@
+// Runs against an in-process solana-test-validator, so no network is needed.
+// The program is built with `cargo build-sbf` on first use; set BPF_OUT_DIR to
+// load a prebuilt subscription_manager.so instead.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::bpf_loader;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use solana_test_validator::{ProgramInfo, TestValidatorGenesis};
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account;
+use subscription_manager::instructions::CancelMode;
+use subscription_manager::state::ScheduleBucket;
+use subscription_manager::{accounts, instruction};
+
+use subscription_keeper::retry::RetryPolicy;
+use subscription_keeper::{pda, scanner, Keeper, KeeperConfig, RunSummary};
+
+const MONTHLY_AMOUNT: u64 = 10 * 1_000_000; // 10 USDC
+const SOL_PRICE: u64 = 50 * 1_000_000; // 50 USDC per SOL
+
+async fn send(rpc: &RpcClient, payer: &Keypair, instructions: &[Instruction], signers: &[&Keypair]) {
+    let blockhash = rpc.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, blockhash);
+    rpc.send_and_confirm_transaction(&tx).await.unwrap();
+}
+
+/// Path to the program binary, building it once per test run unless
+/// BPF_OUT_DIR points at a prebuilt one
+fn program_path() -> PathBuf {
+    static BUILD: Once = Once::new();
+
+    let out_dir = match std::env::var_os("BPF_OUT_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let out_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/deploy");
+            BUILD.call_once(|| {
+                let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("../smart contract logic/Cargo.toml");
+                let status = Command::new("cargo")
+                    .arg("build-sbf")
+                    .arg("--manifest-path")
+                    .arg(&manifest)
+                    .arg("--sbf-out-dir")
+                    .arg(&out_dir)
+                    .status()
+                    .expect("cargo build-sbf is required to build the program");
+                assert!(status.success(), "building subscription_manager failed");
+            });
+            out_dir
+        }
+    };
+    out_dir.join("subscription_manager.so")
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+struct Fixture {
+    rpc: RpcClient,
+    payer: Keypair,
+    mint: Pubkey,
+    user: Keypair,
+    subscription: Pubkey,
+    escrow: Pubkey,
+    schedule_bucket: Pubkey,
+    recipient: Pubkey,
+    _validator: solana_test_validator::TestValidator,
+}
+
+async fn setup() -> Fixture {
+    // Mock oracle account in the adapter's layout: discriminator then price
+    let price_oracle = Pubkey::new_unique();
+    let mut oracle_data = vec![0u8; 8];
+    oracle_data.extend_from_slice(&SOL_PRICE.to_le_bytes());
+
+    let mut genesis = TestValidatorGenesis::default();
+    genesis.add_programs_with_path(&[ProgramInfo {
+        program_id: subscription_manager::ID,
+        loader: bpf_loader::id(),
+        program_path: program_path(),
+    }]);
+    genesis.add_account(
+        price_oracle,
+        AccountSharedData::from(Account {
+            lamports: 1_000_000_000,
+            data: oracle_data,
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        }),
+    );
+    let (validator, payer) = genesis.start_async().await;
+    let rpc = validator.get_async_rpc_client();
+
+    let mint = Keypair::new();
+    let user = Keypair::new();
+    let merchant = Keypair::new();
+    let fee_wallet = Keypair::new();
+    let subscription = pda::subscription(&user.pubkey());
+    let escrow = get_associated_token_address(&subscription, &mint.pubkey());
+    let recipient = get_associated_token_address(&merchant.pubkey(), &mint.pubkey());
+
+    let mint_rent = rpc
+        .get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN)
+        .await
+        .unwrap();
+    send(
+        &rpc,
+        &payer,
+        &[
+            system_instruction::create_account(&payer.pubkey(), &mint.pubkey(), mint_rent, spl_token::state::Mint::LEN as u64, &spl_token::ID),
+            spl_token::instruction::initialize_mint(&spl_token::ID, &mint.pubkey(), &payer.pubkey(), None, 6).unwrap(),
+            system_instruction::transfer(&payer.pubkey(), &user.pubkey(), 1_000_000_000),
+            create_associated_token_account(&payer.pubkey(), &subscription, &mint.pubkey(), &spl_token::ID),
+            create_associated_token_account(&payer.pubkey(), &merchant.pubkey(), &mint.pubkey(), &spl_token::ID),
+            create_associated_token_account(&payer.pubkey(), &fee_wallet.pubkey(), &mint.pubkey(), &spl_token::ID),
+            create_associated_token_account(&payer.pubkey(), &payer.pubkey(), &mint.pubkey(), &spl_token::ID),
+            spl_token::instruction::mint_to(&spl_token::ID, &mint.pubkey(), &escrow, &payer.pubkey(), &[], 10 * MONTHLY_AMOUNT).unwrap(),
+        ],
+        &[&mint],
+    )
+    .await;
+
+    let today = now();
+    let schedule_bucket = pda::schedule_bucket(today, 0);
+    send(
+        &rpc,
+        &payer,
+        &[
+            Instruction {
+                program_id: subscription_manager::ID,
+                accounts: accounts::InitPlatformConfig {
+                    platform_config: pda::platform_config(),
+                    admin: payer.pubkey(),
+                    system_program: solana_sdk::system_program::ID,
+                }
+                .to_account_metas(None),
+                data: instruction::InitPlatformConfig {
+                    fee_wallet: fee_wallet.pubkey(),
+                    price_oracle,
+                }
+                .data(),
+            },
+            Instruction {
+                program_id: subscription_manager::ID,
+                accounts: accounts::OpenScheduleBucket {
+                    schedule_bucket,
+                    payer: payer.pubkey(),
+                    system_program: solana_sdk::system_program::ID,
+                }
+                .to_account_metas(None),
+                data: instruction::OpenScheduleBucket {
+                    day: ScheduleBucket::day_of(today),
+                    page: 0,
+                }
+                .data(),
+            },
+            Instruction {
+                program_id: subscription_manager::ID,
+                accounts: accounts::InitializeSubscription {
+                    subscription,
+                    user: user.pubkey(),
+                    fee_wallet: fee_wallet.pubkey(),
+                    escrow_token_account: escrow,
+                    recipient_token_account: recipient,
+                    schedule_bucket,
+                    plan: None,
+                    coupon: None,
+                    coupon_redemption: None,
+                    referrer: None,
+                    token_program: spl_token::ID,
+                    system_program: solana_sdk::system_program::ID,
+                }
+                .to_account_metas(None),
+                data: instruction::InitializeSubscription {
+                    monthly_amount: MONTHLY_AMOUNT,
+                    fee_wallet: fee_wallet.pubkey(),
+                }
+                .data(),
+            },
+        ],
+        &[&user],
+    )
+    .await;
+
+    Fixture {
+        rpc,
+        payer,
+        mint: mint.pubkey(),
+        user,
+        subscription,
+        escrow,
+        schedule_bucket,
+        recipient,
+        _validator: validator,
+    }
+}
+
+fn keeper(fixture: &Fixture) -> Keeper {
+    Keeper::new(
+        RpcClient::new(fixture.rpc.url()),
+        fixture.payer.insecure_clone(),
+        KeeperConfig {
+            usdc_mint: fixture.mint,
+            retry: RetryPolicy::default(),
+        },
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keeper_charges_due_subscription_once() {
+    let fixture = setup().await;
+    let keeper = keeper(&fixture);
+
+    let summary = keeper.charge_due(now()).await.unwrap();
+    assert_eq!(summary, RunSummary { charged: 1, finalized: 0, released: 0, not_due: 0, failed: 0 });
+
+    let recipient_balance = fixture
+        .rpc
+        .get_token_account_balance(&fixture.recipient)
+        .await
+        .unwrap();
+    assert_eq!(recipient_balance.amount, MONTHLY_AMOUNT.to_string());
+
+    // The next period isn't due yet, so a second scan leaves it alone
+    let summary = keeper.charge_due(now()).await.unwrap();
+    assert_eq!(summary, RunSummary { charged: 0, finalized: 0, released: 0, not_due: 1, failed: 0 });
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scanner_finds_active_subscriptions() {
+    let fixture = setup().await;
+
+    let subscriptions = scanner::active_subscriptions(&fixture.rpc).await.unwrap();
+    assert_eq!(subscriptions.len(), 1);
+
+    let (key, subscription) = &subscriptions[0];
+    assert_eq!(*key, fixture.subscription);
+    assert!(scanner::is_due(subscription, now()));
+    assert!(!scanner::is_due(subscription, subscription.next_payment_due - 1));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_keeper_finalizes_period_end_cancel() {
+    let fixture = setup().await;
+    let keeper = keeper(&fixture);
+    let refund_to = get_associated_token_address(&fixture.user.pubkey(), &fixture.mint);
+
+    // A fresh subscription is due immediately, so its period ends right away
+    send(
+        &fixture.rpc,
+        &fixture.payer,
+        &[
+            create_associated_token_account(&fixture.payer.pubkey(), &fixture.user.pubkey(), &fixture.mint, &spl_token::ID),
+            Instruction {
+                program_id: subscription_manager::ID,
+                accounts: accounts::CancelSubscription {
+                    subscription: fixture.subscription,
+                    user: fixture.user.pubkey(),
+                    subscription_signer: fixture.subscription,
+                    escrow_token_account: fixture.escrow,
+                    destination_token_account: refund_to,
+                    recipient_token_account: fixture.recipient,
+                    schedule_bucket: fixture.schedule_bucket,
+                    pending_refund: None,
+                    token_program: spl_token::ID,
+                    system_program: solana_sdk::system_program::ID,
+                }
+                .to_account_metas(None),
+                data: instruction::CancelSubscription {
+                    mode: CancelMode::AtPeriodEnd,
+                }
+                .data(),
+            },
+        ],
+        &[&fixture.user],
+    )
+    .await;
+
+    let summary = keeper.charge_due(now()).await.unwrap();
+    assert_eq!(summary, RunSummary { charged: 0, finalized: 1, released: 0, not_due: 0, failed: 0 });
+
+    let refunded = fixture.rpc.get_token_account_balance(&refund_to).await.unwrap();
+    assert_eq!(refunded.amount, (10 * MONTHLY_AMOUNT).to_string());
+    assert!(scanner::active_subscriptions(&fixture.rpc).await.unwrap().is_empty());
+}
+
+#[test]
+fn test_retry_delay_backs_off_to_cap() {
+    let policy = RetryPolicy::default();
+
+    assert_eq!(policy.delay(1), policy.initial_delay);
+    assert_eq!(policy.delay(2), policy.initial_delay * 2);
+    assert_eq!(policy.delay(20), policy.max_delay);
+}
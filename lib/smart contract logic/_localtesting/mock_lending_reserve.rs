@@ -0,0 +1,189 @@
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program::{invoke, invoke_signed};
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_program::{entrypoint, msg};
+
+// Minimal stand-in for an SPL token-lending reserve, for local tests of the
+// escrow lending adapter. Reserve accounts use the SPL layout the adapter
+// parses, but only the fields it reads are kept up to date. No borrowing:
+// interest is simulated with ACCRUE_INTEREST after minting the same amount
+// of liquidity straight into the supply account.
+
+const REFRESH_RESERVE: u8 = 3;
+const DEPOSIT_RESERVE_LIQUIDITY: u8 = 4;
+const REDEEM_RESERVE_COLLATERAL: u8 = 5;
+const ACCRUE_INTEREST: u8 = 254;
+
+// Same offsets as lending.rs
+const LENDING_MARKET_OFFSET: usize = 1 + 8 + 1;
+const LIQUIDITY_SUPPLY_OFFSET: usize = LENDING_MARKET_OFFSET + 32 + 32 + 1;
+const AVAILABLE_AMOUNT_OFFSET: usize = LIQUIDITY_SUPPLY_OFFSET + 32 + 32 + 32;
+const BORROWED_WADS_OFFSET: usize = AVAILABLE_AMOUNT_OFFSET + 8;
+const COLLATERAL_MINT_OFFSET: usize = BORROWED_WADS_OFFSET + 16 + 16 + 16;
+const COLLATERAL_SUPPLY_OFFSET: usize = COLLATERAL_MINT_OFFSET + 32;
+
+entrypoint!(process_instruction);
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (tag, amount) = instruction_data
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    if *tag == REFRESH_RESERVE {
+        // Interest only moves through ACCRUE_INTEREST, so there is nothing to refresh
+        msg!("Refreshed reserve");
+        return Ok(());
+    }
+    let amount = u64::from_le_bytes(amount.try_into().map_err(|_| ProgramError::InvalidInstructionData)?);
+
+    match *tag {
+        DEPOSIT_RESERVE_LIQUIDITY => deposit(program_id, accounts, amount),
+        REDEEM_RESERVE_COLLATERAL => redeem(program_id, accounts, amount),
+        ACCRUE_INTEREST => accrue(accounts, amount),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+/// Collateral and liquidity exchange at total_liquidity / collateral_supply
+fn exchange(numerator: u64, reserve_data: &[u8], to_collateral: bool) -> u64 {
+    let liquidity = read_u64(reserve_data, AVAILABLE_AMOUNT_OFFSET) as u128;
+    let collateral = read_u64(reserve_data, COLLATERAL_SUPPLY_OFFSET) as u128;
+    if liquidity == 0 || collateral == 0 {
+        return numerator;
+    }
+    if to_collateral {
+        (numerator as u128 * collateral / liquidity) as u64
+    } else {
+        (numerator as u128 * liquidity / collateral) as u64
+    }
+}
+
+fn deposit(program_id: &Pubkey, accounts: &[AccountInfo], liquidity: u64) -> ProgramResult {
+    let iter = &mut accounts.iter();
+    let source_liquidity = next_account_info(iter)?;
+    let destination_collateral = next_account_info(iter)?;
+    let reserve = next_account_info(iter)?;
+    let liquidity_supply = next_account_info(iter)?;
+    let collateral_mint = next_account_info(iter)?;
+    let lending_market = next_account_info(iter)?;
+    let market_authority = next_account_info(iter)?;
+    let transfer_authority = next_account_info(iter)?;
+    let _clock = next_account_info(iter)?;
+    let token_program = next_account_info(iter)?;
+
+    let (_, authority_bump) = Pubkey::find_program_address(&[lending_market.key.as_ref()], program_id);
+    let authority_seeds: &[&[u8]] = &[lending_market.key.as_ref(), &[authority_bump]];
+
+    let mut data = reserve.try_borrow_mut_data()?;
+    let collateral = exchange(liquidity, &data, true);
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source_liquidity.key,
+            liquidity_supply.key,
+            transfer_authority.key,
+            &[],
+            liquidity,
+        )?,
+        &[source_liquidity.clone(), liquidity_supply.clone(), transfer_authority.clone(), token_program.clone()],
+    )?;
+
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            token_program.key,
+            collateral_mint.key,
+            destination_collateral.key,
+            market_authority.key,
+            &[],
+            collateral,
+        )?,
+        &[collateral_mint.clone(), destination_collateral.clone(), market_authority.clone(), token_program.clone()],
+        &[authority_seeds],
+    )?;
+
+    let available = read_u64(&data, AVAILABLE_AMOUNT_OFFSET);
+    let supply = read_u64(&data, COLLATERAL_SUPPLY_OFFSET);
+    write_u64(&mut data, AVAILABLE_AMOUNT_OFFSET, available + liquidity);
+    write_u64(&mut data, COLLATERAL_SUPPLY_OFFSET, supply + collateral);
+
+    msg!("Deposited {} liquidity for {} collateral", liquidity, collateral);
+    Ok(())
+}
+
+fn redeem(program_id: &Pubkey, accounts: &[AccountInfo], collateral: u64) -> ProgramResult {
+    let iter = &mut accounts.iter();
+    let source_collateral = next_account_info(iter)?;
+    let destination_liquidity = next_account_info(iter)?;
+    let reserve = next_account_info(iter)?;
+    let collateral_mint = next_account_info(iter)?;
+    let liquidity_supply = next_account_info(iter)?;
+    let lending_market = next_account_info(iter)?;
+    let market_authority = next_account_info(iter)?;
+    let transfer_authority = next_account_info(iter)?;
+    let _clock = next_account_info(iter)?;
+    let token_program = next_account_info(iter)?;
+
+    let (_, authority_bump) = Pubkey::find_program_address(&[lending_market.key.as_ref()], program_id);
+    let authority_seeds: &[&[u8]] = &[lending_market.key.as_ref(), &[authority_bump]];
+
+    let mut data = reserve.try_borrow_mut_data()?;
+    let liquidity = exchange(collateral, &data, false);
+
+    invoke(
+        &spl_token::instruction::burn(
+            token_program.key,
+            source_collateral.key,
+            collateral_mint.key,
+            transfer_authority.key,
+            &[],
+            collateral,
+        )?,
+        &[source_collateral.clone(), collateral_mint.clone(), transfer_authority.clone(), token_program.clone()],
+    )?;
+
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            liquidity_supply.key,
+            destination_liquidity.key,
+            market_authority.key,
+            &[],
+            liquidity,
+        )?,
+        &[liquidity_supply.clone(), destination_liquidity.clone(), market_authority.clone(), token_program.clone()],
+        &[authority_seeds],
+    )?;
+
+    let available = read_u64(&data, AVAILABLE_AMOUNT_OFFSET);
+    let supply = read_u64(&data, COLLATERAL_SUPPLY_OFFSET);
+    write_u64(&mut data, AVAILABLE_AMOUNT_OFFSET, available - liquidity);
+    write_u64(&mut data, COLLATERAL_SUPPLY_OFFSET, supply - collateral);
+
+    msg!("Redeemed {} collateral for {} liquidity", collateral, liquidity);
+    Ok(())
+}
+
+/// Test hook: credit interest to the reserve without minting collateral
+fn accrue(accounts: &[AccountInfo], interest: u64) -> ProgramResult {
+    let reserve = next_account_info(&mut accounts.iter())?;
+    let mut data = reserve.try_borrow_mut_data()?;
+    let available = read_u64(&data, AVAILABLE_AMOUNT_OFFSET);
+    write_u64(&mut data, AVAILABLE_AMOUNT_OFFSET, available + interest);
+
+    msg!("Accrued {} interest", interest);
+    Ok(())
+}
@@ -0,0 +1,494 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::token::{self, TokenAccount, Transfer};
+use crate::state::{Subscription, PlatformConfig, ScheduleBucket, Plan, PayoutSplit, Payout};
+use crate::state::constants::BPS_DENOMINATOR;
+use crate::error::SubscriptionError;
+
+pub const FEE_AMOUNT_USDC: u64 = 10_000; // 0.10 USDC with 6 decimals
+
+/// Amounts moved out of escrow for a single period's charge
+pub struct Charge {
+    pub amount: u64,
+    pub platform_fee: u64,
+    pub keeper_reward: u64,
+    pub gas_reimbursement: u64,
+
+    // Referred subscriptions: part of the platform's fee paid to the referrer
+    pub referral_fee: u64,
+
+    // Prorated plans keep `amount` in escrow for the new period and pay the
+    // merchant the previous period's hold instead
+    pub prorated: bool,
+    pub released_hold: u64,
+
+    // Downgrade credit taken off this period's price
+    pub credit_applied: u64,
+
+    // Coupon discount taken off this period's price
+    pub discount: u64,
+
+    // Metered plans: usage reported over the period just ended, paid to the
+    // merchant straight away even on prorated plans
+    pub usage_amount: u64,
+
+    // Add-on periods due with this charge, also paid to the merchant straight away
+    pub add_ons: Vec<AddOnCharge>,
+}
+
+/// One add-on's part of a charge, itemized in PaymentProcessed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct AddOnCharge {
+    pub id: u64,
+    pub periods: u64,
+    pub amount: u64,
+}
+
+impl Charge {
+    /// Everything except the keeper's gas reimbursement
+    pub fn base_total(&self) -> Result<u64> {
+        checked_sum([
+            self.amount,
+            self.extras()?,
+            self.released_hold,
+            self.platform_fee,
+            self.referral_fee,
+            self.keeper_reward,
+        ])
+    }
+
+    /// Usage and add-ons billed on top of the period's price
+    pub fn extras(&self) -> Result<u64> {
+        checked_sum(
+            std::iter::once(self.usage_amount).chain(self.add_ons.iter().map(|add_on| add_on.amount)),
+        )
+    }
+
+    /// What the merchant receives now
+    pub fn merchant_payout(&self) -> Result<u64> {
+        let period = if self.prorated {
+            self.released_hold
+        } else {
+            self.amount
+        };
+        checked_sum([period, self.extras()?])
+    }
+
+    pub fn total(&self) -> Result<u64> {
+        checked_sum([self.base_total()?, self.gas_reimbursement])
+    }
+
+    /// What this charge costs the subscriber. A released hold was already
+    /// paid for in the previous period.
+    pub fn period_total(&self) -> Result<u64> {
+        Ok(self.total()? - self.released_hold)
+    }
+}
+
+/// Add up token amounts, failing rather than wrapping on overflow
+pub fn checked_sum(amounts: impl IntoIterator<Item = u64>) -> Result<u64> {
+    amounts
+        .into_iter()
+        .try_fold(0u64, |total, amount| total.checked_add(amount))
+        .ok_or_else(|| SubscriptionError::MathOverflow.into())
+}
+
+/// Token accounts touched by a charge, all as raw account infos so batch
+/// processing can pass entries straight from `remaining_accounts`
+pub struct ChargeAccounts<'info> {
+    pub escrow_token_account: AccountInfo<'info>,
+    pub recipient_token_account: AccountInfo<'info>,
+    pub fee_wallet_token_account: AccountInfo<'info>,
+    pub keeper_token_account: AccountInfo<'info>,
+    pub subscription_signer: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+
+    // Only needed when the charge owes a referral share
+    pub referrer_token_account: Option<AccountInfo<'info>>,
+
+    // The merchant's payout split and its payee token accounts, in order;
+    // without one the merchant's part goes to recipient_token_account
+    pub payout_split: Option<(PayoutSplit, Vec<AccountInfo<'info>>)>,
+}
+
+/// Load the merchant's payout split, if it has one, and check the payee
+/// accounts passed for it. `payout_split` is the PDA at
+/// [b"payout-split", recipient]; it is empty unless a split was set. A payee
+/// that can't receive is paid through `recipient_token_account` instead, as
+/// in payee_or_recipient.
+pub fn load_payout_split<'info>(
+    payout_split: &AccountInfo<'info>,
+    recipient_token_account: &AccountInfo<'info>,
+    payees: &[AccountInfo<'info>],
+) -> Result<Option<(PayoutSplit, Vec<AccountInfo<'info>>)>> {
+    let Some(split) = read_payout_split(payout_split)? else {
+        return Ok(None);
+    };
+    require!(
+        payees.len() == split.payees.len()
+            && payees
+                .iter()
+                .zip(&split.payees)
+                .all(|(info, payee)| info.key() == payee.token_account),
+        SubscriptionError::PayeeMismatch
+    );
+
+    let payees = payees
+        .iter()
+        .map(|info| payee_or_recipient(info, recipient_token_account))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some((split, payees)))
+}
+
+/// The merchant's payout split, or None while no split is set
+pub fn read_payout_split(payout_split: &AccountInfo) -> Result<Option<PayoutSplit>> {
+    if payout_split.owner != &crate::ID || payout_split.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(PayoutSplit::try_deserialize(&mut &payout_split.try_borrow_data()?[..])?))
+}
+
+/// How a held charge's `amount` is shared, fixed when it is charged: the
+/// split in force then, or all of it to the merchant's payout account
+pub fn held_payouts(payout_split: &AccountInfo, recipient_token_account: Pubkey, amount: u64) -> Result<Vec<Payout>> {
+    Ok(match read_payout_split(payout_split)? {
+        Some(split) => split
+            .payees
+            .iter()
+            .zip(split.split(amount))
+            .map(|(payee, amount)| Payout { token_account: payee.token_account, amount })
+            .collect(),
+        None => vec![Payout { token_account: recipient_token_account, amount }],
+    })
+}
+
+/// `payee`, or `recipient_token_account` when the payee's token account
+/// can't receive the recipient's mint (closed, never created, another mint,
+/// or frozen) and would otherwise block every payout
+pub fn payee_or_recipient<'info>(
+    payee: &AccountInfo<'info>,
+    recipient_token_account: &AccountInfo<'info>,
+) -> Result<AccountInfo<'info>> {
+    let mint = TokenAccount::try_deserialize(&mut &recipient_token_account.try_borrow_data()?[..])?.mint;
+    let receivable = payee.owner == &token::ID
+        && TokenAccount::try_deserialize(&mut &payee.try_borrow_data()?[..])
+            .is_ok_and(|account| account.mint == mint && !account.is_frozen());
+    Ok(if receivable { payee.clone() } else { recipient_token_account.clone() })
+}
+
+/// Ensure the subscription is active, not winding down, and its next payment is due
+pub fn check_due(subscription: &Subscription, now: i64) -> Result<()> {
+    if !subscription.is_active {
+        return Err(SubscriptionError::SubscriptionInactive.into());
+    }
+
+    require!(subscription.stream_rate == 0, SubscriptionError::StreamNotBillable);
+
+    require!(
+        subscription.cancel_at.is_none(),
+        SubscriptionError::SubscriptionEnding
+    );
+
+    require!(
+        now >= subscription.next_payment_due,
+        SubscriptionError::PaymentNotDue
+    );
+    Ok(())
+}
+
+/// Funds a charge can draw on. Delegated subscriptions are limited by what
+/// the user's token account has approved for the subscription PDA.
+pub fn available_funds(subscription: &Account<Subscription>, funding_account: &TokenAccount) -> u64 {
+    if !subscription.delegated {
+        return funding_account.amount;
+    }
+    if funding_account.delegate != COption::Some(subscription.key()) {
+        return 0;
+    }
+    funding_account.amount.min(funding_account.delegated_amount)
+}
+
+/// Whether taking `additional` more from the subscriber keeps both the
+/// current period's total (now `period_total`) and the lifetime total within
+/// the caps they set
+pub fn within_spending_cap(subscription: &Subscription, period_total: u64, additional: u64) -> bool {
+    let period_ok = subscription.max_amount_per_period == 0
+        || period_total <= subscription.max_amount_per_period;
+    let lifetime_ok = subscription
+        .lifetime_cap
+        .map_or(true, |cap| {
+            subscription
+                .lifetime_charged
+                .checked_add(additional)
+                .is_some_and(|total| total <= cap)
+        });
+    period_ok && lifetime_ok
+}
+
+/// Bring a due plan subscription's price in line with its plan before it is
+/// charged. Price decreases apply, and so do increases up to a price the
+/// subscriber accepted for this plan once that acceptance has taken effect;
+/// an increase they never accepted instead ends the subscription with the
+/// period already paid for, refunding to `refund_destination`. Returns
+/// whether the subscription may still be charged.
+pub fn sync_plan_price(
+    subscription: &mut Subscription,
+    plan: &Plan,
+    refund_destination: Pubkey,
+) -> bool {
+    // Usage over the period just ended is billed at this plan's current unit price
+    subscription.unit_price = plan.unit_price;
+
+    // A deferred downgrade replaces the plan at this charge anyway
+    if subscription.pending_plan.is_some() {
+        return true;
+    }
+
+    let due = subscription.next_payment_due;
+    let price = plan.price_at(due);
+    let accepted = subscription
+        .accepted_price
+        .filter(|accepted| subscription.plan == Some(accepted.plan) && accepted.effective_at <= due);
+    if price <= subscription.monthly_amount || accepted.is_some_and(|accepted| price <= accepted.price) {
+        subscription.monthly_amount = price;
+        // An acceptance is used up once its effective time has come
+        if accepted.is_some() {
+            subscription.accepted_price = None;
+        }
+        return true;
+    }
+
+    subscription.cancel_at = Some(subscription.next_payment_due);
+    subscription.refund_destination = Some(refund_destination);
+    false
+}
+
+/// Price of the period about to be charged, after any deferred plan change
+pub fn period_price(subscription: &Subscription) -> u64 {
+    if subscription.pending_plan.is_some() {
+        subscription.pending_price
+    } else {
+        subscription.monthly_amount
+    }
+}
+
+/// Reported usage owed at the subscription's unit price
+pub fn usage_due(subscription: &Subscription) -> Result<u64> {
+    subscription
+        .usage_units
+        .checked_mul(subscription.unit_price)
+        .ok_or_else(|| SubscriptionError::InvalidAmount.into())
+}
+
+/// Reported usage a closing subscription still owes the merchant, limited to
+/// `available` and to what the subscriber's spending caps would let its last
+/// charge collect
+pub fn closing_usage(subscription: &Subscription, available: u64) -> Result<u64> {
+    let mut usage = usage_due(subscription)?.min(available);
+    if subscription.max_amount_per_period > 0 {
+        usage = usage.min(subscription.max_amount_per_period);
+    }
+    if let Some(cap) = subscription.lifetime_cap {
+        usage = usage.min(cap.saturating_sub(subscription.lifetime_charged));
+    }
+    Ok(usage)
+}
+
+/// Work out the period's charge, splitting the platform fee with the keeper
+/// and any referrer, and adding every add-on period that has come due by `now`
+pub fn compute_charge(
+    subscription: &Subscription,
+    platform_config: &PlatformConfig,
+    gas_reimbursement: u64,
+    now: i64,
+) -> Result<Charge> {
+    let keeper_reward = FEE_AMOUNT_USDC * platform_config.keeper_reward_bps as u64 / BPS_DENOMINATOR;
+    let platform_share = FEE_AMOUNT_USDC - keeper_reward;
+    let referral_fee = if subscription.referrer.is_some()
+        && subscription.referral_periods_paid < platform_config.referral_periods
+    {
+        platform_share * platform_config.referral_share_bps as u64 / BPS_DENOMINATOR
+    } else {
+        0
+    };
+
+    let price = period_price(subscription);
+    let discount = match subscription.coupon_discount {
+        Some(coupon_discount) if subscription.coupon_periods_left > 0 => coupon_discount.off(price),
+        _ => 0,
+    };
+    let price = price - discount;
+    let credit_applied = subscription.credit.min(price);
+
+    Ok(Charge {
+        amount: price - credit_applied,
+        platform_fee: platform_share - referral_fee,
+        keeper_reward,
+        gas_reimbursement,
+        referral_fee,
+        prorated: subscription.prorated,
+        released_hold: subscription.held_amount,
+        credit_applied,
+        discount,
+        usage_amount: usage_due(subscription)?,
+        add_ons: subscription
+            .add_ons
+            .iter()
+            .map(|add_on| {
+                let periods = add_on.periods_due(now);
+                let amount = periods.checked_mul(add_on.price).ok_or(SubscriptionError::MathOverflow)?;
+                Ok((periods > 0).then_some(AddOnCharge {
+                    id: add_on.id,
+                    periods,
+                    amount,
+                }))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// Move a charge out of escrow, signing as the subscription PDA. For delegated
+/// subscriptions the PDA signs as the user's approved delegate instead.
+pub fn transfer_charge<'info>(
+    subscription: &Subscription,
+    charge: &Charge,
+    accounts: &ChargeAccounts<'info>,
+) -> Result<()> {
+    let seeds = &[
+        b"subscription",
+        subscription.user.as_ref(),
+        &[subscription.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let transfer = |to: &AccountInfo<'info>, amount: u64| -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        token::transfer(
+            CpiContext::new_with_signer(
+                accounts.token_program.clone(),
+                Transfer {
+                    from: accounts.escrow_token_account.clone(),
+                    to: to.clone(),
+                    authority: accounts.subscription_signer.clone(),
+                },
+                signer,
+            ),
+            amount,
+        )
+    };
+
+    // Main payment to the merchant or its payees, the platform's share of the fee,
+    // then the keeper's reward and gas reimbursement in one transfer
+    match &accounts.payout_split {
+        Some((split, payees)) => {
+            for (payee, amount) in payees.iter().zip(split.split(charge.merchant_payout()?)) {
+                transfer(payee, amount)?;
+            }
+        }
+        None => transfer(&accounts.recipient_token_account, charge.merchant_payout()?)?,
+    }
+    transfer(&accounts.fee_wallet_token_account, charge.platform_fee)?;
+    if charge.referral_fee > 0 {
+        let referrer_token_account = accounts
+            .referrer_token_account
+            .as_ref()
+            .ok_or(SubscriptionError::ReferrerRequired)?;
+        transfer(referrer_token_account, charge.referral_fee)?;
+    }
+    transfer(
+        &accounts.keeper_token_account,
+        checked_sum([charge.keeper_reward, charge.gas_reimbursement])?,
+    )?;
+
+    Ok(())
+}
+
+/// Day bucket the subscription moves into once the current period is charged
+pub fn next_schedule_day(subscription: &Subscription) -> i64 {
+    ScheduleBucket::day_of(subscription.next_payment_due + subscription.interval_seconds)
+}
+
+/// Advance the billing schedule after a successful charge, refiling the
+/// subscription from its current schedule bucket into the next one
+pub fn record_payment(
+    subscription: &mut Account<Subscription>,
+    charge: &Charge,
+    now: i64,
+    due_bucket: &mut ScheduleBucket,
+    next_bucket: &mut ScheduleBucket,
+) -> Result<()> {
+    let key = subscription.key();
+
+    due_bucket.remove(&key);
+    next_bucket.insert(key)?;
+
+    subscription.last_payment_timestamp = Some(now);
+    subscription.next_payment_due += subscription.interval_seconds;
+    subscription.schedule_page = next_bucket.page;
+    subscription.held_amount = if charge.prorated { charge.amount } else { 0 };
+    subscription.credit -= charge.credit_applied;
+    subscription.usage_units = 0;
+    if charge.referral_fee > 0 {
+        subscription.referral_periods_paid += 1;
+    }
+    if subscription.coupon_periods_left > 0 {
+        subscription.coupon_periods_left -= 1;
+        if subscription.coupon_periods_left == 0 {
+            subscription.coupon_discount = None;
+        }
+    }
+    for billed in &charge.add_ons {
+        if let Some(add_on) = subscription.add_ons.iter_mut().find(|add_on| add_on.id == billed.id) {
+            add_on.next_due += billed.periods as i64 * add_on.interval_seconds;
+        }
+    }
+    subscription.period_charged = charge.period_total()?;
+    subscription.lifetime_charged = checked_sum([subscription.lifetime_charged, subscription.period_charged])?;
+    subscription.past_due = false;
+
+    // A deferred downgrade takes effect with the period just charged. Plans
+    // only change within the same billing mode, so `prorated` carries over.
+    if let Some(plan) = subscription.pending_plan.take() {
+        subscription.plan = Some(plan);
+        subscription.monthly_amount = subscription.pending_price;
+        subscription.unit_price = subscription.pending_unit_price;
+        subscription.pending_price = 0;
+        subscription.pending_unit_price = 0;
+        subscription.accepted_price = None;
+    }
+
+    Ok(())
+}
+
+/// What a stream owes the merchant for the time since it was last paid out,
+/// and the time that payment covers up to. Once the escrow runs dry the
+/// stream only pays for the seconds the balance can cover.
+pub fn stream_accrued(subscription: &Subscription, escrow_balance: u64, now: i64) -> (u64, i64) {
+    let rate = subscription.stream_rate;
+    let elapsed = (now - subscription.streamed_until).max(0) as u128;
+    let owed = elapsed * rate as u128;
+
+    if owed <= escrow_balance as u128 {
+        return (owed as u64, now.max(subscription.streamed_until));
+    }
+
+    let covered = (escrow_balance / rate) as i64;
+    (covered as u64 * rate, subscription.streamed_until + covered)
+}
+
+/// Split a prorated subscription's held payment into the part the merchant
+/// has earned by `now` and the unused remainder owed back to the subscriber
+pub fn split_hold(subscription: &Subscription, now: i64) -> (u64, u64) {
+    let held = subscription.held_amount;
+    if held == 0 || subscription.interval_seconds <= 0 {
+        return (0, 0);
+    }
+
+    let period_start = subscription.next_payment_due - subscription.interval_seconds;
+    let elapsed = (now - period_start).clamp(0, subscription.interval_seconds);
+    let earned = (held as u128 * elapsed as u128 / subscription.interval_seconds as u128) as u64;
+    (earned, held - earned)
+}
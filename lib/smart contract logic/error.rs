@@ -41,4 +41,136 @@ pub enum SubscriptionError {
 
     #[msg("Unable to unstake the escrow funds.")]
     UnstakeFailed, // The process of unstaking failed.
+
+    #[msg("The price oracle returned an invalid price.")]
+    InvalidOraclePrice, // The oracle account is malformed or reports a zero price.
+
+    #[msg("The subscription payment is not due yet.")]
+    PaymentNotDue, // A keeper tried to charge before next_payment_due.
+
+    #[msg("The payment batch is malformed or too large.")]
+    InvalidBatch, // remaining_accounts is empty, not in triples, or over MAX_BATCH_SIZE.
+
+    #[msg("The schedule bucket does not match the subscription's due date.")]
+    ScheduleMismatch, // Wrong day or page passed for the subscription's schedule entry.
+
+    #[msg("The schedule bucket page is full.")]
+    ScheduleBucketFull, // Caller should retry with the next page for that day.
+
+    #[msg("The subscription already has a stake account.")]
+    StakeAccountExists, // Only one stake account per subscription at a time.
+
+    #[msg("The stake account does not belong to this subscription.")]
+    InvalidStakeAccount, // Stake account missing or not the one recorded on the subscription.
+
+    #[msg("The stake has not been deactivated.")]
+    StakeNotDeactivated, // deactivate_stake must run before withdraw_stake.
+
+    #[msg("The stake is still cooling down.")]
+    StakeCooldownPending, // Withdrawal is only possible after the deactivation epoch.
+
+    #[msg("The validator is not on the approved list.")]
+    ValidatorNotApproved, // Escrowed SOL may only be delegated to admin-approved validators.
+
+    #[msg("Invalid stake pool.")]
+    InvalidStakePool, // Not the approved pool, not owned by the stake pool program, or mint mismatch.
+
+    #[msg("Invalid lending reserve.")]
+    InvalidLendingReserve, // Not the configured reserve, not owned by the lending program, or collateral mint mismatch.
+
+    #[msg("A pending refund record is required.")]
+    PendingRefundRequired, // Canceling with staked, pooled or lent escrow must open a pending refund.
+
+    #[msg("The subscription ends with the current period.")]
+    SubscriptionEnding, // Canceled at period end, so no further charges are taken.
+
+    #[msg("The current period has not ended yet.")]
+    PeriodNotEnded, // An at-period-end cancel can only be finalized once next_payment_due is reached.
+
+    #[msg("Plans must share a merchant, payout account and billing interval.")]
+    PlanMismatch, // change_plan can only move between tiers of the same merchant offering.
+
+    #[msg("The subscription's plan account is required.")]
+    PlanRequired, // Plan subscriptions must be charged against their plan so price changes apply.
+
+    #[msg("No price change is pending.")]
+    NoPriceChange, // accept_price_change was called on a plan without an announced increase.
+
+    #[msg("The charge would exceed the subscriber's spending cap.")]
+    SpendingCapExceeded, // A period's total or the lifetime total would pass the user-set limit.
+
+    #[msg("Not available for delegated subscriptions.")]
+    DelegatedModeUnsupported, // Prorated holds and lending need escrow the program controls.
+
+    #[msg("This subscription is not a stream.")]
+    NotStreaming, // withdraw_stream and stop_stream only apply to streaming subscriptions.
+
+    #[msg("Streams are paid out continuously, not charged per period.")]
+    StreamNotBillable, // Streaming subscriptions are never filed for keepers or charged by process_payment, and end through stop_stream rather than cancel.
+
+    #[msg("The plan does not bill for usage.")]
+    NotMetered, // Usage can only be reported against plans with a unit price.
+
+    #[msg("The subscription already has the maximum number of add-ons.")]
+    TooManyAddOns, // Add-ons are stored inline, up to Subscription::MAX_ADD_ONS.
+
+    #[msg("An add-on with this id is already attached.")]
+    DuplicateAddOn, // Add-on ids must be unique within a subscription.
+
+    #[msg("No add-on with this id is attached.")]
+    AddOnNotFound, // detach_add_on was given an unknown id.
+
+    #[msg("The coupon has expired or been fully redeemed.")]
+    CouponUnavailable, // Past expires_at or max_redemptions reached.
+
+    #[msg("A coupon is already being applied to this subscription.")]
+    CouponActive, // Only one coupon discount runs at a time.
+
+    #[msg("The subscription's referrer accounts are required.")]
+    ReferrerRequired, // Charges that owe a referral share must carry the referrer's stats and token account.
+
+    #[msg("Payee accounts don't match the merchant's payout split.")]
+    PayeeMismatch, // Payee token accounts must follow the payout split, in order.
+
+    #[msg("The merchant's settlement accounts are required.")]
+    SettlementRequired, // While the platform holds settlements, charges must pay into the merchant's settlement vault.
+
+    #[msg("The held payment is frozen by a dispute.")]
+    PaymentDisputed, // Disputed payments are released only by the arbiter's resolution.
+
+    #[msg("The payment is still inside its dispute window.")]
+    PaymentStillHeld, // Held payments are released once their release time has passed.
+
+    #[msg("The dispute window for this payment has closed.")]
+    DisputeWindowClosed, // Disputes must be opened before the payment's release time.
+
+    #[msg("The dispute has already been resolved.")]
+    DisputeClosed, // Evidence and resolutions are only accepted while a dispute is open.
+
+    #[msg("The evidence URI is too long.")]
+    EvidenceUriTooLong, // Evidence URIs are stored inline, up to Dispute::MAX_URI_LEN bytes.
+
+    #[msg("This coupon has already been redeemed on this subscription.")]
+    CouponAlreadyRedeemed, // A subscription gets each coupon's discount once, even after it runs out or is replaced.
+
+    #[msg("No arbiter has been appointed.")]
+    ArbiterRequired, // Disputes can only be opened while an arbiter can resolve them.
+
+    #[msg("The schedule bucket is still in use.")]
+    ScheduleBucketInUse, // Only empty pages for days that have already passed can be closed.
+
+    #[msg("Arithmetic overflow.")]
+    MathOverflow, // Token amounts that don't fit in a u64 are rejected rather than wrapped.
+
+    #[msg("The settlement is owed to too many payees; claim it first.")]
+    SettlementFull, // Released payments are tracked for up to Settlement::MAX_PAYOUTS payees between claims.
+
+    #[msg("The coupon redemption account doesn't match the coupon and subscription.")]
+    InvalidCouponRedemption, // Redeeming a coupon records it at [b"redemption", coupon, subscription].
+
+    #[msg("Not available for streaming subscriptions.")]
+    StreamModeUnsupported, // A stream's escrow must stay liquid so withdraw_stream and stop_stream can pay it out.
+
+    #[msg("Part of the escrow is still staked, pooled or lent.")]
+    EscrowNotLiquid, // stop_stream pays out the escrow directly, so every position must be unwound first.
 }
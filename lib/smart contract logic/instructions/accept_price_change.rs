@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use crate::state::{Subscription, Plan, AcceptedPrice};
+use crate::error::SubscriptionError;
+use crate::billing::{self, FEE_AMOUNT_USDC};
+
+#[derive(Accounts)]
+pub struct AcceptPriceChange<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    pub user: Signer<'info>,
+
+    #[account(
+        constraint = subscription.plan == Some(plan.key()) @ SubscriptionError::PlanRequired
+    )]
+    pub plan: Account<'info, Plan>,
+}
+
+/// Agree to the plan's announced price, which then applies from the first
+/// period starting on or after its effective time
+pub fn handler(ctx: Context<AcceptPriceChange>) -> Result<()> {
+    let plan = &ctx.accounts.plan;
+    require!(plan.pending_price > 0, SubscriptionError::NoPriceChange);
+
+    require!(
+        billing::within_spending_cap(&ctx.accounts.subscription, plan.pending_price + FEE_AMOUNT_USDC, 0),
+        SubscriptionError::SpendingCapExceeded
+    );
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.accepted_price = Some(AcceptedPrice {
+        plan: plan.key(),
+        price: plan.pending_price,
+        effective_at: plan.price_effective_at,
+    });
+
+    emit!(PriceChangeAccepted {
+        user: subscription.user,
+        plan: plan.key(),
+        price: plan.pending_price,
+        effective_at: plan.price_effective_at,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct PriceChangeAccepted {
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub price: u64,
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::state::{Subscription, Coupon, CouponRedemption};
+use crate::instructions::stake_escrow::create_pda;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct ApplyCoupon<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub coupon: Account<'info, Coupon>,
+
+    /// CHECK: The coupon's CouponRedemption PDA for this subscription, checked
+    /// and created by redeem_coupon
+    #[account(mut)]
+    pub coupon_redemption: UncheckedAccount<'info>,
+
+    // Coupons only apply to subscriptions paying the merchant that issued them
+    #[account(
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized,
+        constraint = recipient_token_account.owner == coupon.merchant @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<ApplyCoupon>) -> Result<()> {
+    require!(ctx.accounts.subscription.is_active, SubscriptionError::SubscriptionInactive);
+    redeem_coupon(
+        &mut ctx.accounts.subscription,
+        &mut ctx.accounts.coupon,
+        &ctx.accounts.coupon_redemption,
+        &ctx.accounts.user,
+        &ctx.accounts.system_program,
+        Clock::get()?.unix_timestamp,
+    )
+}
+
+/// Count a redemption against the coupon, record it at
+/// [b"redemption", coupon, subscription], paid for by `payer`, and start its
+/// discount with the subscription's next charge
+pub(crate) fn redeem_coupon<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    coupon: &mut Account<'info, Coupon>,
+    coupon_redemption: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    now: i64,
+) -> Result<()> {
+    let (coupon_key, subscription_key) = (coupon.key(), subscription.key());
+    let (address, bump) = Pubkey::find_program_address(
+        &[b"redemption", coupon_key.as_ref(), subscription_key.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(coupon_redemption.key(), address, SubscriptionError::InvalidCouponRedemption);
+
+    require!(coupon.is_redeemable(now), SubscriptionError::CouponUnavailable);
+    require!(subscription.coupon_periods_left == 0, SubscriptionError::CouponActive);
+    // Each subscriber gets a coupon's discount once, even after it runs out
+    // or another coupon has replaced it
+    require!(coupon_redemption.owner != &crate::ID, SubscriptionError::CouponAlreadyRedeemed);
+
+    let seeds: &[&[u8]] = &[b"redemption", coupon_key.as_ref(), subscription_key.as_ref(), &[bump]];
+    let space = 8 + CouponRedemption::LEN;
+    create_pda(
+        &payer.to_account_info(),
+        coupon_redemption,
+        &system_program.to_account_info(),
+        Rent::get()?.minimum_balance(space),
+        space,
+        &crate::ID,
+        seeds,
+    )?;
+    CouponRedemption {
+        coupon: coupon_key,
+        subscription: subscription_key,
+        redeemed_at: now,
+        bump,
+    }
+    .try_serialize(&mut &mut coupon_redemption.try_borrow_mut_data()?[..])?;
+
+    coupon.redemptions += 1;
+    subscription.coupon = Some(coupon.key());
+    subscription.coupon_discount = Some(coupon.discount);
+    subscription.coupon_periods_left = coupon.duration_periods;
+
+    emit!(CouponRedeemed {
+        user: subscription.user,
+        coupon: coupon.key(),
+        merchant: coupon.merchant,
+        duration_periods: coupon.duration_periods,
+        redemptions: coupon.redemptions,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct CouponRedeemed {
+    pub user: Pubkey,
+    pub coupon: Pubkey,
+    pub merchant: Pubkey,
+    pub duration_periods: u32,
+    // Redemptions of this coupon so far, including this one
+    pub redemptions: u32,
+    pub timestamp: i64,
+}
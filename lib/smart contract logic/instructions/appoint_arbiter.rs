@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct AppointArbiter<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+/// Open disputes pass to the new arbiter, so the seat can't be left empty
+pub fn handler(ctx: Context<AppointArbiter>, arbiter: Pubkey) -> Result<()> {
+    require!(arbiter != Pubkey::default(), SubscriptionError::ArbiterRequired);
+    ctx.accounts.platform_config.arbiter = arbiter;
+
+    emit!(ArbiterAppointed {
+        arbiter,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct ArbiterAppointed {
+    pub arbiter: Pubkey,
+    pub timestamp: i64,
+}
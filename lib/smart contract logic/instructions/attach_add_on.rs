@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::state::{Subscription, AddOn};
+use crate::error::SubscriptionError;
+use crate::billing::{self, FEE_AMOUNT_USDC};
+
+#[derive(Accounts)]
+pub struct AttachAddOn<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    pub user: Signer<'info>,
+
+    // The merchant offering the add-on co-signs through its payout account
+    #[account(
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized,
+        token::authority = merchant
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub merchant: Signer<'info>,
+}
+
+/// Attach a recurring add-on agreed between the user and the merchant. Its
+/// first period is billed with the subscription's next charge.
+pub fn handler(
+    ctx: Context<AttachAddOn>,
+    id: u64,
+    price: u64,
+    interval_seconds: i64,
+) -> Result<()> {
+    require!(price > 0 && interval_seconds > 0, SubscriptionError::InvalidAmount);
+
+    let subscription = &mut ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(subscription.stream_rate == 0, SubscriptionError::StreamNotBillable);
+    require!(
+        subscription.add_ons.iter().all(|add_on| add_on.id != id),
+        SubscriptionError::DuplicateAddOn
+    );
+    require!(
+        subscription.add_ons.len() < Subscription::MAX_ADD_ONS,
+        SubscriptionError::TooManyAddOns
+    );
+
+    let next_due = subscription.next_payment_due;
+    let add_on = AddOn {
+        id,
+        price,
+        interval_seconds,
+        next_due,
+    };
+
+    // Refuse an add-on the subscriber's cap wouldn't let the next charge collect
+    let add_on_charges = subscription
+        .add_ons
+        .iter()
+        .chain(std::iter::once(&add_on))
+        .map(|add_on| {
+            add_on
+                .periods_due(next_due)
+                .checked_mul(add_on.price)
+                .ok_or_else(|| SubscriptionError::MathOverflow.into())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let next_charge = billing::checked_sum(
+        [billing::period_price(subscription), FEE_AMOUNT_USDC, billing::usage_due(subscription)?]
+            .into_iter()
+            .chain(add_on_charges),
+    )?;
+    require!(
+        billing::within_spending_cap(subscription, next_charge, next_charge),
+        SubscriptionError::SpendingCapExceeded
+    );
+
+    subscription.add_ons.push(add_on);
+
+    emit!(AddOnAttached {
+        user: subscription.user,
+        id,
+        price,
+        interval_seconds,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct AddOnAttached {
+    pub user: Pubkey,
+    pub id: u64,
+    pub price: u64,
+    pub interval_seconds: i64,
+    pub timestamp: i64,
+}
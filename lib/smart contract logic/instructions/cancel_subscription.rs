@@ -1,7 +1,16 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
-use crate::state::Subscription;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::{Subscription, ScheduleBucket, PendingRefund};
 use crate::error::SubscriptionError;
+use crate::billing;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CancelMode {
+    // Refund everything now, including the current period
+    Immediate,
+    // Keep the current period; no further charges, refund once it ends
+    AtPeriodEnd,
+}
 
 #[derive(Accounts)]
 pub struct CancelSubscription<'info> {
@@ -18,54 +27,180 @@ pub struct CancelSubscription<'info> {
     )]
     pub subscription_signer: AccountInfo<'info>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
     pub escrow_token_account: Account<'info, TokenAccount>,
 
     #[account(mut)]
     pub destination_token_account: Account<'info, TokenAccount>,
 
+    // Receives the used part of a prorated subscription's held payment
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = schedule_bucket.holds(&subscription) @ SubscriptionError::ScheduleMismatch
+    )]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    // Only needed when part of the escrow is staked, pooled or lent
+    #[account(
+        init,
+        payer = user,
+        space = 8 + PendingRefund::LEN,
+        seeds = [b"pending-refund", subscription.key().as_ref()],
+        bump
+    )]
+    pub pending_refund: Option<Account<'info, PendingRefund>>,
+
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<CancelSubscription>) -> Result<()> {
+pub fn handler(ctx: Context<CancelSubscription>, mode: CancelMode) -> Result<()> {
     let subscription = &mut ctx.accounts.subscription;
 
     if !subscription.is_active {
         return Err(SubscriptionError::SubscriptionInactive.into());
     }
+    // A stream's accrued amount belongs to the merchant; stop_stream pays it
+    // out before refunding, close_out would refund it to the user
+    require!(subscription.stream_rate == 0, SubscriptionError::StreamNotBillable);
+
+    let now = Clock::get()?.unix_timestamp;
+
+    if mode == CancelMode::AtPeriodEnd {
+        // Paid through next_payment_due; finalize_cancellation refunds after that
+        subscription.cancel_at = Some(subscription.next_payment_due);
+        subscription.refund_destination = Some(ctx.accounts.destination_token_account.key());
+
+        emit!(SubscriptionCancelScheduled {
+            user: subscription.user,
+            cancel_at: subscription.next_payment_due,
+            timestamp: now,
+        });
+        return Ok(());
+    }
 
-    let remaining_balance = ctx.accounts.escrow_token_account.amount;
-
-    if remaining_balance > 0 {
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.escrow_token_account.to_account_info(),
-            to: ctx.accounts.destination_token_account.to_account_info(),
-            authority: ctx.accounts.subscription_signer.clone(),
-        };
-
-        let seeds = &[
-            b"subscription",
-            ctx.accounts.user.key.as_ref(),
-            &[subscription.bump],
-        ];
-        let signer = &[&seeds[..]];
+    let bump = ctx.bumps.pending_refund;
+    close_out(
+        subscription,
+        &ctx.accounts.escrow_token_account,
+        &ctx.accounts.destination_token_account,
+        &ctx.accounts.recipient_token_account,
+        &ctx.accounts.subscription_signer,
+        &ctx.accounts.token_program,
+        &mut ctx.accounts.schedule_bucket,
+        ctx.accounts.pending_refund.as_mut().map(|pending_refund| (pending_refund, bump)),
+        now,
+    )
+}
 
+/// Refund the liquid escrow, deactivate the subscription and take it off the
+/// payment schedule. A prorated subscription's held payment is split by the
+/// time used so far: the merchant keeps the used part and the rest is
+/// refunded. Usage reported this period is paid to the merchant first.
+/// Staked, pooled or lent escrow can't be returned in the same transaction,
+/// so it is recorded in a pending refund and paid out by settle_refund_stake,
+/// settle_refund_stake_pool or settle_refund_lending. Delegated subscriptions
+/// have nothing to refund; the user revokes the approval from their own wallet.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn close_out<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    escrow_token_account: &Account<'info, TokenAccount>,
+    destination_token_account: &Account<'info, TokenAccount>,
+    recipient_token_account: &Account<'info, TokenAccount>,
+    subscription_signer: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    schedule_bucket: &mut ScheduleBucket,
+    pending_refund: Option<(&mut Account<'info, PendingRefund>, u8)>,
+    now: i64,
+) -> Result<()> {
+    let (merchant_amount, prorated_refund) = billing::split_hold(subscription, now);
+    require!(
+        escrow_token_account.amount >= merchant_amount,
+        SubscriptionError::InsufficientFunds
+    );
+    // Usage reported this period goes out with it, as its next charge would have billed it
+    let usage_amount = billing::closing_usage(
+        subscription,
+        billing::available_funds(subscription, escrow_token_account).saturating_sub(merchant_amount),
+    )?;
+    // A delegated subscription's funds never left the user's wallet
+    let remaining_balance = if subscription.delegated {
+        0
+    } else {
+        escrow_token_account.amount - merchant_amount - usage_amount
+    };
+
+    let seeds = &[
+        b"subscription",
+        subscription.user.as_ref(),
+        &[subscription.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    for (to, amount) in [
+        (recipient_token_account, merchant_amount + usage_amount),
+        (destination_token_account, remaining_balance),
+    ] {
+        if amount == 0 {
+            continue;
+        }
         token::transfer(
             CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
+                token_program.to_account_info(),
+                Transfer {
+                    from: escrow_token_account.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: subscription_signer.clone(),
+                },
                 signer,
             ),
-            remaining_balance,
+            amount,
         )?;
     }
 
     subscription.is_active = false;
+    subscription.held_amount = 0;
+    subscription.usage_units = 0;
+    subscription.lifetime_charged = billing::checked_sum([subscription.lifetime_charged, usage_amount])?;
+    subscription.cancel_at = None;
+    subscription.refund_destination = None;
+    schedule_bucket.remove(&subscription.key());
+
+    let refund_pending = subscription.has_illiquid_positions();
+
+    if refund_pending {
+        let (pending_refund, bump) =
+            pending_refund.ok_or(SubscriptionError::PendingRefundRequired)?;
+
+        pending_refund.set_inner(PendingRefund {
+            user: subscription.user,
+            subscription: subscription.key(),
+            destination_token_account: destination_token_account.key(),
+            stake_lamports: subscription.staked_balance.unwrap_or_default(),
+            pool_tokens: subscription.pool_token_balance,
+            lent_collateral: subscription.lent_collateral,
+            created_at: now,
+            bump,
+        });
+    }
 
     emit!(SubscriptionCanceled {
-        user: ctx.accounts.user.key(),
+        user: subscription.user,
         refunded_amount: remaining_balance,
-        timestamp: Clock::get()?.unix_timestamp,
+        merchant_amount,
+        prorated_refund,
+        usage_amount,
+        refund_pending,
+        timestamp: now,
     });
 
     Ok(())
@@ -74,6 +209,21 @@ pub fn handler(ctx: Context<CancelSubscription>) -> Result<()> {
 #[event]
 pub struct SubscriptionCanceled {
     pub user: Pubkey,
+    // Everything returned to the subscriber now, including prorated_refund
     pub refunded_amount: u64,
+    // Prorated plans: the used part of the held payment kept by the merchant
+    pub merchant_amount: u64,
+    // Prorated plans: the unused part of the held payment returned to the subscriber
+    pub prorated_refund: u64,
+    // Reported usage paid to the merchant before refunding
+    pub usage_amount: u64,
+    pub refund_pending: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubscriptionCancelScheduled {
+    pub user: Pubkey,
+    pub cancel_at: i64,
     pub timestamp: i64,
 }
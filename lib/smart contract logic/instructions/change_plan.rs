@@ -0,0 +1,196 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::{Subscription, Plan};
+use crate::error::SubscriptionError;
+use crate::billing::{self, FEE_AMOUNT_USDC};
+
+#[derive(Accounts)]
+pub struct ChangePlan<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", user.key().as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    #[account(
+        constraint = subscription.plan == Some(current_plan.key()) @ SubscriptionError::PlanMismatch
+    )]
+    pub current_plan: Account<'info, Plan>,
+
+    #[account(
+        constraint = new_plan.key() != current_plan.key() @ SubscriptionError::PlanMismatch,
+        constraint = new_plan.merchant == current_plan.merchant @ SubscriptionError::PlanMismatch,
+        constraint = new_plan.recipient_token_account == current_plan.recipient_token_account @ SubscriptionError::PlanMismatch,
+        constraint = new_plan.interval_seconds == current_plan.interval_seconds @ SubscriptionError::PlanMismatch,
+        // Switching billing mode would strand or double count the held payment
+        constraint = new_plan.prorated == current_plan.prorated @ SubscriptionError::PlanMismatch
+    )]
+    pub new_plan: Account<'info, Plan>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Move to another tier of the same merchant. Upgrades take effect now and
+/// charge the price difference for what's left of the current period.
+/// Downgrades either take effect now, crediting the unused difference against
+/// upcoming charges, or are deferred to the next period.
+pub fn handler(ctx: Context<ChangePlan>, defer_downgrade: bool) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(
+        subscription.cancel_at.is_none(),
+        SubscriptionError::SubscriptionEnding
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    let old_price = subscription.monthly_amount;
+    let new_price = ctx.accounts.new_plan.price_at(now);
+    let is_downgrade = new_price < old_price;
+
+    // The new price plus fees must fit the subscriber's cap from the next period on
+    require!(
+        billing::within_spending_cap(subscription, new_price + FEE_AMOUNT_USDC, 0),
+        SubscriptionError::SpendingCapExceeded
+    );
+
+    if is_downgrade && defer_downgrade {
+        let subscription = &mut ctx.accounts.subscription;
+        subscription.pending_plan = Some(ctx.accounts.new_plan.key());
+        subscription.pending_price = new_price;
+        subscription.pending_unit_price = ctx.accounts.new_plan.unit_price;
+        subscription.accepted_price = None;
+
+        emit!(PlanChanged {
+            user: subscription.user,
+            old_plan: ctx.accounts.current_plan.key(),
+            new_plan: ctx.accounts.new_plan.key(),
+            old_price,
+            new_price,
+            prorated_charge: 0,
+            prorated_credit: 0,
+            effective_at: subscription.next_payment_due,
+            timestamp: now,
+        });
+        return Ok(());
+    }
+
+    // Share of the current period still to run. Before the first charge
+    // nothing has been paid at the old price, so there is nothing to prorate.
+    let interval = subscription.interval_seconds;
+    let remaining = if subscription.last_payment_timestamp.is_some() {
+        (subscription.next_payment_due - now).clamp(0, interval)
+    } else {
+        0
+    };
+    let prorate = |difference: u64| (difference as u128 * remaining as u128 / interval as u128) as u64;
+
+    let (prorated_charge, prorated_credit) = if is_downgrade {
+        (0, prorate(old_price - new_price))
+    } else {
+        (prorate(new_price - old_price), 0)
+    };
+
+    if prorated_charge > 0 {
+        require!(
+            billing::within_spending_cap(
+                subscription,
+                subscription.period_charged + prorated_charge,
+                prorated_charge,
+            ),
+            SubscriptionError::SpendingCapExceeded
+        );
+
+        // Prorated plans keep the top-up with the held payment; others pay the merchant now
+        let outflow = if subscription.prorated { 0 } else { prorated_charge };
+        require!(
+            billing::available_funds(subscription, &ctx.accounts.escrow_token_account) >= subscription.held_amount + prorated_charge,
+            SubscriptionError::InsufficientFunds
+        );
+
+        if outflow > 0 {
+            let seeds = &[
+                b"subscription",
+                subscription.user.as_ref(),
+                &[subscription.bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.escrow_token_account.to_account_info(),
+                        to: ctx.accounts.recipient_token_account.to_account_info(),
+                        authority: ctx.accounts.subscription_signer.clone(),
+                    },
+                    signer,
+                ),
+                outflow,
+            )?;
+        }
+    }
+
+    let subscription = &mut ctx.accounts.subscription;
+    if subscription.prorated {
+        // The held payment tracks what the merchant is owed for this period
+        subscription.held_amount =
+            billing::checked_sum([subscription.held_amount, prorated_charge])?.saturating_sub(prorated_credit);
+    } else {
+        subscription.credit = billing::checked_sum([subscription.credit, prorated_credit])?;
+    }
+    subscription.period_charged = billing::checked_sum([subscription.period_charged, prorated_charge])?;
+    subscription.lifetime_charged = billing::checked_sum([subscription.lifetime_charged, prorated_charge])?;
+    subscription.plan = Some(ctx.accounts.new_plan.key());
+    subscription.monthly_amount = new_price;
+    subscription.unit_price = ctx.accounts.new_plan.unit_price;
+    subscription.pending_plan = None;
+    subscription.pending_price = 0;
+    subscription.pending_unit_price = 0;
+    subscription.accepted_price = None;
+
+    emit!(PlanChanged {
+        user: subscription.user,
+        old_plan: ctx.accounts.current_plan.key(),
+        new_plan: ctx.accounts.new_plan.key(),
+        old_price,
+        new_price,
+        prorated_charge,
+        prorated_credit,
+        effective_at: now,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct PlanChanged {
+    pub user: Pubkey,
+    pub old_plan: Pubkey,
+    pub new_plan: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+    pub prorated_charge: u64,
+    pub prorated_credit: u64,
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,105 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::Settlement;
+use crate::error::SubscriptionError;
+use crate::billing;
+
+#[derive(Accounts)]
+pub struct ClaimSettlement<'info> {
+    #[account(
+        mut,
+        seeds = [b"settlement", recipient_token_account.key().as_ref()],
+        bump = settlement.bump
+    )]
+    pub settlement: Account<'info, Settlement>,
+
+    #[account(
+        mut,
+        seeds = [b"settlement-vault", recipient_token_account.key().as_ref()],
+        bump
+    )]
+    pub settlement_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::authority = merchant
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub merchant: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Pay the merchant every payment released so far by release_held_payment
+/// or a dispute resolution. Each payee recorded in the settlement's payouts
+/// is paid its part; their token accounts follow as remaining accounts, in
+/// order, and a payee that can't receive is paid through the payout account.
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimSettlement<'info>>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    let settlement = &mut ctx.accounts.settlement;
+    let amount = settlement.released;
+    require!(amount > 0, SubscriptionError::InvalidAmount);
+    let payouts = std::mem::take(&mut settlement.payouts);
+    settlement.released = 0;
+
+    require!(
+        ctx.remaining_accounts.len() == payouts.len()
+            && ctx
+                .remaining_accounts
+                .iter()
+                .zip(&payouts)
+                .all(|(info, payout)| info.key() == payout.token_account),
+        SubscriptionError::PayeeMismatch
+    );
+
+    let recipient_key = ctx.accounts.recipient_token_account.key();
+    let seeds = &[
+        b"settlement",
+        recipient_key.as_ref(),
+        &[ctx.accounts.settlement.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let transfer = |to: AccountInfo<'info>, amount: u64| -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.settlement_vault.to_account_info(),
+                    to,
+                    authority: ctx.accounts.settlement.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )
+    };
+
+    let recipient_token_account = ctx.accounts.recipient_token_account.to_account_info();
+    for (payee, payout) in ctx.remaining_accounts.iter().zip(&payouts) {
+        transfer(billing::payee_or_recipient(payee, &recipient_token_account)?, payout.amount)?;
+    }
+
+    emit!(SettlementClaimed {
+        recipient_token_account: recipient_key,
+        amount,
+        still_held: ctx.accounts.settlement.held,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct SettlementClaimed {
+    pub recipient_token_account: Pubkey,
+    pub amount: u64,
+    // Payments still inside their window or frozen by a dispute
+    pub still_held: u64,
+    pub timestamp: i64,
+}
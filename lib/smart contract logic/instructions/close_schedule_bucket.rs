@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+use crate::state::ScheduleBucket;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct CloseScheduleBucket<'info> {
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"schedule",
+            schedule_bucket.day.to_le_bytes().as_ref(),
+            schedule_bucket.page.to_le_bytes().as_ref()
+        ],
+        bump = schedule_bucket.bump,
+        constraint = schedule_bucket.subscriptions.is_empty() @ SubscriptionError::ScheduleBucketInUse
+    )]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    /// CHECK: Receives the page's rent
+    #[account(mut, address = schedule_bucket.payer @ SubscriptionError::Unauthorized)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Permissionless: close an emptied schedule page for a day that has already
+/// passed and refund its rent to whoever opened it. Nothing is filed under a
+/// past day unless a late charge lands there, which reopens the page.
+pub fn handler(ctx: Context<CloseScheduleBucket>) -> Result<()> {
+    let today = ScheduleBucket::day_of(Clock::get()?.unix_timestamp);
+    require!(
+        ctx.accounts.schedule_bucket.day < today,
+        SubscriptionError::ScheduleBucketInUse
+    );
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+use crate::state::{Coupon, Discount};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+#[instruction(code: [u8; 16])]
+pub struct CreateCoupon<'info> {
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + Coupon::LEN,
+        seeds = [b"coupon", merchant.key().as_ref(), code.as_ref()],
+        bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<CreateCoupon>,
+    code: [u8; 16],
+    discount: Discount,
+    duration_periods: u32,
+    max_redemptions: u32,
+    expires_at: Option<i64>,
+) -> Result<()> {
+    require!(
+        discount.is_valid() && duration_periods > 0,
+        SubscriptionError::InvalidAmount
+    );
+
+    ctx.accounts.coupon.set_inner(Coupon {
+        merchant: ctx.accounts.merchant.key(),
+        code,
+        discount,
+        duration_periods,
+        max_redemptions,
+        redemptions: 0,
+        expires_at,
+        bump: ctx.bumps.coupon,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::state::{Plan, YieldSplit};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+#[instruction(plan_id: u64)]
+pub struct CreatePlan<'info> {
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + Plan::LEN,
+        seeds = [b"plan", merchant.key().as_ref(), plan_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub plan: Account<'info, Plan>,
+
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    #[account(
+        constraint = recipient_token_account.owner == merchant.key() @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handler(
+    ctx: Context<CreatePlan>,
+    plan_id: u64,
+    price: u64,
+    interval_seconds: i64,
+    prorated: bool,
+    yield_split: Option<YieldSplit>,
+    unit_price: u64,
+    setup_fee: u64,
+) -> Result<()> {
+    require!(price > 0 && interval_seconds > 0, SubscriptionError::InvalidAmount);
+    require!(
+        yield_split.map_or(true, |split| split.is_valid()),
+        SubscriptionError::InvalidAmount
+    );
+
+    ctx.accounts.plan.set_inner(Plan {
+        merchant: ctx.accounts.merchant.key(),
+        plan_id,
+        recipient_token_account: ctx.accounts.recipient_token_account.key(),
+        price,
+        interval_seconds,
+        prorated,
+        yield_split,
+        bump: ctx.bumps.plan,
+        pending_price: 0,
+        price_effective_at: 0,
+        unit_price,
+        setup_fee,
+    });
+
+    Ok(())
+}
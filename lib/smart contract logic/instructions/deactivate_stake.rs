@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake;
+use anchor_lang::solana_program::stake::instruction as stake_instruction;
+use crate::state::Subscription;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct DeactivateStake<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Must be the subscription's stake PDA recorded by stake_escrow
+    #[account(
+        mut,
+        seeds = [b"stake", subscription.key().as_ref()],
+        bump,
+        constraint = subscription.stake_account == Some(stake_account.key()) @ SubscriptionError::InvalidStakeAccount
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Phase two of the staking lifecycle: start the cooldown. Funds become
+/// withdrawable once the epoch after `stake_deactivation_epoch` begins.
+pub fn handler(ctx: Context<DeactivateStake>) -> Result<()> {
+    require!(
+        ctx.accounts.subscription.stake_deactivation_epoch.is_none(),
+        SubscriptionError::InvalidSubscriptionState
+    );
+
+    let deactivate_ix = stake_instruction::deactivate_stake(
+        &ctx.accounts.stake_account.key(),
+        &ctx.accounts.subscription_signer.key(),
+    );
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &deactivate_ix,
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.stake_program.to_account_info(),
+        ],
+        &[&[
+            b"subscription",
+            ctx.accounts.subscription.user.as_ref(),
+            &[ctx.accounts.subscription.bump],
+        ][..]],
+    )?;
+
+    // Rewards earned while delegated land in the stake account's lamports
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.staked_balance = Some(ctx.accounts.stake_account.lamports());
+    subscription.stake_deactivation_epoch = Some(ctx.accounts.clock.epoch);
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{Subscription, PlatformConfig};
+use crate::error::SubscriptionError;
+use crate::stake_pool::{self, STAKE_POOL_PROGRAM_ID};
+
+#[derive(Accounts)]
+pub struct DepositStakePool<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    // Funds the deposit; the pool tokens stay escrowed under the subscription's authority
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    /// CHECK: Parsed by the stake pool adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.approved_stake_pool @ SubscriptionError::InvalidStakePool
+    )]
+    pub stake_pool: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    pub pool_withdraw_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub reserve_stake: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub manager_fee_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+
+    // PDA vault holding the subscription's pool tokens
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"pool-tokens", subscription.key().as_ref()],
+        bump,
+        token::mint = pool_mint,
+        token::authority = subscription
+    )]
+    pub pool_token_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: SPL stake pool program
+    #[account(address = STAKE_POOL_PROGRAM_ID)]
+    pub stake_pool_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Stake SOL from the user's wallet through the approved pool. The pool
+/// tokens are the subscriber's SOL, not escrowed USDC, so charges never draw
+/// on them: the user redeems them with redeem_stake_pool_sol or
+/// redeem_stake_pool_stake, and a cancel redeems whatever is left through
+/// settle_refund_stake_pool.
+pub fn handler(ctx: Context<DepositStakePool>, lamports: u64) -> Result<()> {
+    require!(lamports > 0, SubscriptionError::InvalidAmount);
+    let subscription = &ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(subscription.stream_rate == 0, SubscriptionError::StreamModeUnsupported);
+
+    let pool = stake_pool::read_pool(&ctx.accounts.stake_pool.to_account_info())?;
+    require_keys_eq!(
+        pool.pool_mint,
+        ctx.accounts.pool_mint.key(),
+        SubscriptionError::InvalidStakePool
+    );
+
+    let deposit_ix = stake_pool::deposit_sol(
+        &ctx.accounts.stake_pool.key(),
+        &pool,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.pool_token_vault.key(),
+        lamports,
+    );
+
+    anchor_lang::solana_program::program::invoke(
+        &deposit_ix,
+        &[
+            ctx.accounts.stake_pool.to_account_info(),
+            ctx.accounts.pool_withdraw_authority.to_account_info(),
+            ctx.accounts.reserve_stake.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.pool_token_vault.to_account_info(),
+            ctx.accounts.manager_fee_account.to_account_info(),
+            ctx.accounts.pool_mint.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.stake_pool_program.to_account_info(),
+        ],
+    )?;
+
+    ctx.accounts.pool_token_vault.reload()?;
+    let pool = stake_pool::read_pool(&ctx.accounts.stake_pool.to_account_info())?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.pool_token_balance = ctx.accounts.pool_token_vault.amount;
+    subscription.pool_exchange_rate = pool.exchange_rate();
+
+    emit!(StakePoolDeposited {
+        user: subscription.user,
+        lamports,
+        pool_token_balance: subscription.pool_token_balance,
+        exchange_rate: subscription.pool_exchange_rate,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct StakePoolDeposited {
+    pub user: Pubkey,
+    pub lamports: u64,
+    pub pool_token_balance: u64,
+    pub exchange_rate: u64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::state::Subscription;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct DetachAddOn<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    pub user: Signer<'info>,
+}
+
+/// Drop an add-on. Periods already billed are not refunded.
+pub fn handler(ctx: Context<DetachAddOn>, id: u64) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    let index = subscription
+        .add_ons
+        .iter()
+        .position(|add_on| add_on.id == id)
+        .ok_or(SubscriptionError::AddOnNotFound)?;
+    subscription.add_ons.remove(index);
+
+    emit!(AddOnDetached {
+        user: subscription.user,
+        id,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct AddOnDetached {
+    pub user: Pubkey,
+    pub id: u64,
+    pub timestamp: i64,
+}
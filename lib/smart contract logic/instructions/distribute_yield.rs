@@ -0,0 +1,129 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::{Subscription, PlatformConfig, Plan};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct DistributeYield<'info> {
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = escrow_token_account.mint,
+        constraint = fee_wallet_token_account.owner == platform_config.fee_wallet @ SubscriptionError::Unauthorized
+    )]
+    pub fee_wallet_token_account: Account<'info, TokenAccount>,
+
+    // Required for plan subscriptions, whose plan may override the split
+    #[account(
+        constraint = subscription.plan == Some(plan.key()) @ SubscriptionError::InvalidSubscriptionState
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Permissionless: share out interest realized into escrow according to the
+/// plan's yield split, falling back to the platform's. Does nothing when no
+/// yield is waiting.
+pub fn handler(ctx: Context<DistributeYield>) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    let realized = subscription.accrued_interest;
+    if realized == 0 {
+        return Ok(());
+    }
+
+    require!(
+        subscription.plan.is_none() || ctx.accounts.plan.is_some(),
+        SubscriptionError::InvalidSubscriptionState
+    );
+    let yield_split = ctx
+        .accounts
+        .plan
+        .as_ref()
+        .and_then(|plan| plan.yield_split)
+        .unwrap_or(ctx.accounts.platform_config.yield_split);
+    let (subscriber_amount, merchant_amount, platform_amount) = yield_split.apply(realized);
+    require!(
+        ctx.accounts.escrow_token_account.amount >= merchant_amount + platform_amount,
+        SubscriptionError::InsufficientFunds
+    );
+
+    let seeds = &[
+        b"subscription",
+        subscription.user.as_ref(),
+        &[subscription.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    for (to, amount) in [
+        (ctx.accounts.recipient_token_account.to_account_info(), merchant_amount),
+        (ctx.accounts.fee_wallet_token_account.to_account_info(), platform_amount),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    to,
+                    authority: ctx.accounts.subscription_signer.clone(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+    }
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.accrued_interest = 0;
+    subscription.yield_credited += subscriber_amount;
+
+    emit!(YieldDistributed {
+        user: subscription.user,
+        subscriber_amount,
+        merchant_amount,
+        platform_amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct YieldDistributed {
+    pub user: Pubkey,
+    pub subscriber_amount: u64,
+    pub merchant_amount: u64,
+    pub platform_amount: u64,
+    pub timestamp: i64,
+}
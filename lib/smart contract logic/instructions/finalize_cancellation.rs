@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+use crate::state::{Subscription, ScheduleBucket, PendingRefund};
+use crate::error::SubscriptionError;
+use crate::instructions::cancel_subscription::close_out;
+
+#[derive(Accounts)]
+pub struct FinalizeCancellation<'info> {
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = subscription.refund_destination == Some(destination_token_account.key()) @ SubscriptionError::Unauthorized
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    // Receives a prorated subscription's held payment, fully used by now
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = schedule_bucket.holds(&subscription) @ SubscriptionError::ScheduleMismatch
+    )]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    // Only needed when part of the escrow is staked, pooled or lent
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PendingRefund::LEN,
+        seeds = [b"pending-refund", subscription.key().as_ref()],
+        bump
+    )]
+    pub pending_refund: Option<Account<'info, PendingRefund>>,
+
+    // The user, or the keeper making the final crank
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Permissionless: once a subscription canceled at period end has reached
+/// the end of its paid period, refund what's left in escrow
+pub fn handler(ctx: Context<FinalizeCancellation>) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(subscription.stream_rate == 0, SubscriptionError::StreamNotBillable);
+
+    let cancel_at = subscription
+        .cancel_at
+        .ok_or(SubscriptionError::InvalidSubscriptionState)?;
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= cancel_at, SubscriptionError::PeriodNotEnded);
+
+    let bump = ctx.bumps.pending_refund;
+    close_out(
+        subscription,
+        &ctx.accounts.escrow_token_account,
+        &ctx.accounts.destination_token_account,
+        &ctx.accounts.recipient_token_account,
+        &ctx.accounts.subscription_signer,
+        &ctx.accounts.token_program,
+        &mut ctx.accounts.schedule_bucket,
+        ctx.accounts.pending_refund.as_mut().map(|pending_refund| (pending_refund, bump)),
+        now,
+    )
+}
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use crate::state::PlatformConfig;
+use crate::state::{PlatformConfig, YieldSplit};
+use crate::state::constants::{DEFAULT_GAS_REIMBURSEMENT_LAMPORTS, DEFAULT_KEEPER_REWARD_BPS};
 
 #[derive(Accounts)]
 #[instruction()]
@@ -22,13 +23,26 @@ pub struct InitPlatformConfig<'info> {
 pub fn handler(
     ctx: Context<InitPlatformConfig>,
     fee_wallet: Pubkey,
+    price_oracle: Pubkey,
 ) -> Result<()> {
-    let bump = *ctx.bumps.get("platform_config").unwrap();
+    let bump = ctx.bumps.platform_config;
 
     ctx.accounts.platform_config.set_inner(PlatformConfig {
         fee_wallet,
         admin: ctx.accounts.admin.key(),
         bump,
+        price_oracle,
+        gas_reimbursement_lamports: DEFAULT_GAS_REIMBURSEMENT_LAMPORTS,
+        keeper_reward_bps: DEFAULT_KEEPER_REWARD_BPS,
+        approved_validators: Vec::new(),
+        approved_stake_pool: Pubkey::default(),
+        lending_program: Pubkey::default(),
+        lending_reserve: Pubkey::default(),
+        yield_split: YieldSplit::SUBSCRIBER_ONLY,
+        referral_share_bps: 0,
+        referral_periods: 0,
+        settlement_delay: 0,
+        arbiter: Pubkey::default(),
     });
 
     Ok(())
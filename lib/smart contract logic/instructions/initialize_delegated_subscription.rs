@@ -0,0 +1,105 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Approve, Token, TokenAccount};
+use crate::state::{Subscription, ScheduleBucket, Plan};
+use crate::error::SubscriptionError;
+use crate::instructions::initialize_subscription::{open_subscription, charge_setup_fee};
+
+#[derive(Accounts)]
+pub struct InitializeDelegatedSubscription<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Subscription::LEN,
+        seeds = [b"subscription", user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: fee_wallet is saved as a pubkey, not used for execution logic directly
+    pub fee_wallet: UncheckedAccount<'info>,
+
+    // Charges are pulled from here; the user keeps custody of the funds
+    #[account(
+        mut,
+        token::authority = user
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    // Bound here so keepers can't redirect payments to another account
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == user_token_account.mint
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    // Any open page for today; the first payment is filed here for keepers
+    #[account(mut)]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    // Prorated plans hold payments in escrow, so they need the escrow mode
+    #[account(
+        constraint = plan.recipient_token_account == recipient_token_account.key() @ SubscriptionError::Unauthorized,
+        constraint = !plan.prorated @ SubscriptionError::DelegatedModeUnsupported
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Subscribe without locking funds up front. The user approves the
+/// subscription PDA to spend up to `allowance` from their token account and
+/// each period is pulled from there; the approval can be raised or revoked
+/// at any time with the token program directly.
+pub fn handler(
+    ctx: Context<InitializeDelegatedSubscription>,
+    monthly_amount: u64,
+    fee_wallet: Pubkey,
+    allowance: u64,
+) -> Result<()> {
+    require!(allowance > 0, SubscriptionError::InvalidAmount);
+
+    token::approve(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Approve {
+                to: ctx.accounts.user_token_account.to_account_info(),
+                delegate: ctx.accounts.subscription.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        ),
+        allowance,
+    )?;
+
+    let bump = ctx.bumps.subscription;
+    open_subscription(
+        &mut ctx.accounts.subscription,
+        ctx.accounts.user.key(),
+        ctx.accounts.user_token_account.key(),
+        ctx.accounts.recipient_token_account.key(),
+        monthly_amount,
+        fee_wallet,
+        bump,
+        ctx.accounts.plan.as_ref(),
+        &mut ctx.accounts.schedule_bucket,
+    )?;
+    ctx.accounts.subscription.delegated = true;
+
+    // The user signs this transaction, so the setup fee comes straight from their wallet
+    if let Some(plan) = &ctx.accounts.plan {
+        charge_setup_fee(
+            &mut ctx.accounts.subscription,
+            plan,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.recipient_token_account,
+            ctx.accounts.user.to_account_info(),
+            &ctx.accounts.token_program,
+            &[],
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::state::Subscription;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct InitializeStream<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Subscription::LEN,
+        seeds = [b"subscription", user.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: fee_wallet is saved as a pubkey, not used for execution logic directly
+    pub fee_wallet: UncheckedAccount<'info>,
+
+    // Escrow must be controlled by the subscription PDA so only the program can move funds
+    #[account(
+        constraint = escrow_token_account.owner == subscription.key() @ SubscriptionError::Unauthorized
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    // The merchant withdraws the streamed amount into this account
+    #[account(
+        constraint = recipient_token_account.mint == escrow_token_account.mint
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Open a stream paying the merchant `rate_per_second` out of escrow from
+/// now on. Streams are never put on the payment schedule; the merchant
+/// withdraws what has accrued whenever it likes.
+pub fn handler(
+    ctx: Context<InitializeStream>,
+    rate_per_second: u64,
+    fee_wallet: Pubkey,
+) -> Result<()> {
+    require!(rate_per_second > 0, SubscriptionError::InvalidAmount);
+
+    let now = Clock::get()?.unix_timestamp;
+    let bump = ctx.bumps.subscription;
+
+    let mut subscription = Subscription::new(
+        ctx.accounts.user.key(),
+        ctx.accounts.escrow_token_account.key(),
+        0,
+        fee_wallet,
+        ctx.accounts.recipient_token_account.key(),
+        now,
+        bump,
+    );
+    subscription.stream_rate = rate_per_second;
+    subscription.streamed_until = now;
+    ctx.accounts.subscription.set_inner(subscription);
+
+    emit!(StreamStarted {
+        user: ctx.accounts.user.key(),
+        recipient: ctx.accounts.recipient_token_account.key(),
+        rate_per_second,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct StreamStarted {
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub rate_per_second: u64,
+    pub timestamp: i64,
+}
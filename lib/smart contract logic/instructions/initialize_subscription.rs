@@ -1,5 +1,10 @@
 use anchor_lang::prelude::*;
-use crate::state::Subscription;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::{Subscription, ScheduleBucket, Plan, Coupon, Referrer};
+use crate::instructions::apply_coupon::redeem_coupon;
+use crate::state::constants::BILLING_INTERVAL_SECONDS;
+use crate::error::SubscriptionError;
+use crate::billing;
 
 #[derive(Accounts)]
 #[instruction(monthly_amount: u64)]
@@ -7,7 +12,7 @@ pub struct InitializeSubscription<'info> {
     #[account(
         init,
         payer = user,
-        space = Subscription::LEN,
+        space = 8 + Subscription::LEN,
         seeds = [b"subscription", user.key().as_ref()],
         bump
     )]
@@ -19,6 +24,50 @@ pub struct InitializeSubscription<'info> {
     /// CHECK: fee_wallet is saved as a pubkey, not used for execution logic directly
     pub fee_wallet: UncheckedAccount<'info>,
 
+    // Escrow must be controlled by the subscription PDA so only the program can move funds
+    #[account(
+        mut,
+        constraint = escrow_token_account.owner == subscription.key() @ SubscriptionError::Unauthorized
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    // Bound here so keepers can't redirect payments to another account
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == escrow_token_account.mint
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    // Any open page for today; the first payment is filed here for keepers
+    #[account(mut)]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    // Subscribing to a merchant plan takes its price and billing terms
+    #[account(
+        constraint = plan.recipient_token_account == recipient_token_account.key() @ SubscriptionError::Unauthorized
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    // A promotion from the same merchant, discounting the first charges
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == coupon.merchant @ SubscriptionError::Unauthorized
+    )]
+    pub coupon: Option<Account<'info, Coupon>>,
+
+    /// CHECK: The coupon's CouponRedemption PDA for this subscription, checked
+    /// and created by redeem_coupon; required with a coupon
+    #[account(mut)]
+    pub coupon_redemption: Option<UncheckedAccount<'info>>,
+
+    // Whoever referred the subscriber; earns a share of the platform fee
+    #[account(
+        mut,
+        constraint = referrer.wallet != user.key() @ SubscriptionError::Unauthorized
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+
+    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
@@ -27,13 +76,150 @@ pub fn handler(
     monthly_amount: u64,
     fee_wallet: Pubkey,
 ) -> Result<()> {
-    let subscription = &mut ctx.accounts.subscription;
+    let bump = ctx.bumps.subscription;
+    open_subscription(
+        &mut ctx.accounts.subscription,
+        ctx.accounts.user.key(),
+        ctx.accounts.escrow_token_account.key(),
+        ctx.accounts.recipient_token_account.key(),
+        monthly_amount,
+        fee_wallet,
+        bump,
+        ctx.accounts.plan.as_ref(),
+        &mut ctx.accounts.schedule_bucket,
+    )?;
+
+    if let Some(plan) = &ctx.accounts.plan {
+        let user = ctx.accounts.user.key();
+        let seeds = &[b"subscription", user.as_ref(), &[bump]];
+        let subscription_signer = ctx.accounts.subscription.to_account_info();
+        charge_setup_fee(
+            &mut ctx.accounts.subscription,
+            plan,
+            &ctx.accounts.escrow_token_account,
+            &ctx.accounts.recipient_token_account,
+            subscription_signer,
+            &ctx.accounts.token_program,
+            &[&seeds[..]],
+        )?;
+    }
+
+    if let Some(referrer) = &mut ctx.accounts.referrer {
+        referrer.referred_subscriptions += 1;
+        ctx.accounts.subscription.referrer = Some(referrer.wallet);
+    }
+
+    if let Some(coupon) = &mut ctx.accounts.coupon {
+        let coupon_redemption = ctx
+            .accounts
+            .coupon_redemption
+            .as_ref()
+            .ok_or(SubscriptionError::InvalidCouponRedemption)?;
+        redeem_coupon(
+            &mut ctx.accounts.subscription,
+            coupon,
+            coupon_redemption,
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            Clock::get()?.unix_timestamp,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Pay the plan's one-time setup fee to the merchant, from escrow signed by
+/// the subscription PDA or, for delegated subscriptions, from the user's
+/// wallet signed by the user
+pub(crate) fn charge_setup_fee<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    plan: &Account<'info, Plan>,
+    from: &Account<'info, TokenAccount>,
+    recipient_token_account: &Account<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    signer: &[&[&[u8]]],
+) -> Result<()> {
+    if plan.setup_fee == 0 {
+        return Ok(());
+    }
+    require!(from.amount >= plan.setup_fee, SubscriptionError::InsufficientFunds);
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: from.to_account_info(),
+                to: recipient_token_account.to_account_info(),
+                authority,
+            },
+            signer,
+        ),
+        plan.setup_fee,
+    )?;
+    subscription.lifetime_charged = billing::checked_sum([subscription.lifetime_charged, plan.setup_fee])?;
+
+    emit!(SetupFeeCharged {
+        user: subscription.user,
+        plan: plan.key(),
+        amount: plan.setup_fee,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
 
-    subscription.user = ctx.accounts.user.key();
+/// Fill in a new subscription and file its first payment, which is due now.
+/// Shared by the escrow and delegated modes; `funding_account` is the escrow
+/// or, for delegated subscriptions, the user's own token account.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn open_subscription(
+    subscription: &mut Account<Subscription>,
+    user: Pubkey,
+    funding_account: Pubkey,
+    recipient_token_account: Pubkey,
+    monthly_amount: u64,
+    fee_wallet: Pubkey,
+    bump: u8,
+    plan: Option<&Account<Plan>>,
+    schedule_bucket: &mut ScheduleBucket,
+) -> Result<()> {
+    subscription.user = user;
+    subscription.escrow_token_account = funding_account;
+    subscription.recipient_token_account = recipient_token_account;
     subscription.monthly_amount = monthly_amount;
     subscription.fee_wallet = fee_wallet;
-    subscription.bump = *ctx.bumps.get("subscription").unwrap();
+    subscription.bump = bump;
     subscription.is_active = true;
 
+    // The first period is charged as soon as a keeper picks it up
+    let now = Clock::get()?.unix_timestamp;
+    subscription.interval_seconds = BILLING_INTERVAL_SECONDS;
+    subscription.next_payment_due = now;
+
+    if let Some(plan) = plan {
+        // New subscribers sign up at the plan's current price
+        require!(plan.price_at(now) == monthly_amount, SubscriptionError::InvalidAmount);
+        subscription.plan = Some(plan.key());
+        subscription.interval_seconds = plan.interval_seconds;
+        subscription.prorated = plan.prorated;
+        subscription.unit_price = plan.unit_price;
+    }
+
+    require!(
+        schedule_bucket.day == ScheduleBucket::day_of(now),
+        SubscriptionError::ScheduleMismatch
+    );
+    schedule_bucket.insert(subscription.key())?;
+    subscription.schedule_page = schedule_bucket.page;
+
     Ok(())
 }
+
+#[event]
+pub struct SetupFeeCharged {
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,151 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{Subscription, PlatformConfig};
+use crate::error::SubscriptionError;
+use crate::{billing, lending};
+
+#[derive(Accounts)]
+pub struct LendEscrow<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    // Pays rent for the collateral vault on first use
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: Parsed by the lending adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.lending_reserve @ SubscriptionError::InvalidLendingReserve
+    )]
+    pub lending_reserve: UncheckedAccount<'info>,
+
+    /// CHECK: The reserve's liquidity price oracle, checked against the reserve by the lending adapter
+    pub reserve_liquidity_oracle: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the lending program
+    #[account(mut)]
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub reserve_collateral_mint: Account<'info, Mint>,
+
+    /// CHECK: Validated by the lending program
+    pub lending_market: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the lending program
+    pub lending_market_authority: UncheckedAccount<'info>,
+
+    // PDA vault holding the subscription's reserve collateral tokens
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"collateral", subscription.key().as_ref()],
+        bump,
+        token::mint = reserve_collateral_mint,
+        token::authority = subscription
+    )]
+    pub collateral_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Lending program, address pinned by the platform config
+    #[account(address = platform_config.lending_program @ SubscriptionError::InvalidLendingReserve)]
+    pub lending_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Move idle escrow into the lending reserve. The next period's base charge
+/// always stays liquid; at most the gas reimbursement needs a withdrawal.
+pub fn handler(ctx: Context<LendEscrow>, amount: u64) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(amount > 0, SubscriptionError::InvalidAmount);
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(!subscription.delegated, SubscriptionError::DelegatedModeUnsupported);
+    require!(subscription.stream_rate == 0, SubscriptionError::StreamModeUnsupported);
+
+    let next_charge = billing::compute_charge(
+        subscription,
+        &ctx.accounts.platform_config,
+        0,
+        subscription.next_payment_due,
+    )?;
+    require!(
+        ctx.accounts.escrow_token_account.amount >= billing::checked_sum([amount, next_charge.base_total()?])?,
+        SubscriptionError::InsufficientFunds
+    );
+
+    let reserve = lending::refresh_and_read_reserve(
+        &ctx.accounts.lending_reserve.to_account_info(),
+        &ctx.accounts.reserve_liquidity_oracle.to_account_info(),
+        &ctx.accounts.clock.to_account_info(),
+        &ctx.accounts.lending_program.to_account_info(),
+    )?;
+    require_keys_eq!(
+        reserve.collateral_mint,
+        ctx.accounts.reserve_collateral_mint.key(),
+        SubscriptionError::InvalidLendingReserve
+    );
+
+    let deposit_ix = lending::deposit_liquidity(
+        &ctx.accounts.lending_program.key(),
+        &ctx.accounts.lending_reserve.key(),
+        &reserve,
+        &ctx.accounts.escrow_token_account.key(),
+        &ctx.accounts.collateral_vault.key(),
+        &ctx.accounts.subscription_signer.key(),
+        amount,
+    );
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &deposit_ix,
+        &[
+            ctx.accounts.escrow_token_account.to_account_info(),
+            ctx.accounts.collateral_vault.to_account_info(),
+            ctx.accounts.lending_reserve.to_account_info(),
+            ctx.accounts.reserve_liquidity_supply.to_account_info(),
+            ctx.accounts.reserve_collateral_mint.to_account_info(),
+            ctx.accounts.lending_market.to_account_info(),
+            ctx.accounts.lending_market_authority.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.lending_program.to_account_info(),
+        ],
+        &[&[
+            b"subscription",
+            subscription.user.as_ref(),
+            &[subscription.bump],
+        ][..]],
+    )?;
+
+    ctx.accounts.collateral_vault.reload()?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.lent_principal += amount;
+    subscription.lent_collateral = ctx.accounts.collateral_vault.amount;
+
+    Ok(())
+}
@@ -1,11 +1,107 @@
 pub mod initialize_subscription;
 pub mod process_payment;
+pub mod process_payments_batch;
 pub mod cancel_subscription;
-pub mod update_fee_wallet;
+pub mod update_platform_fee_wallet;
 pub mod stake_escrow;
+pub mod deactivate_stake;
+pub mod withdraw_stake;
+pub mod deposit_stake_pool;
+pub mod redeem_stake_pool_sol;
+pub mod redeem_stake_pool_stake;
+pub mod init_platform_config;
+pub mod update_gas_reimbursement;
+pub mod update_keeper_reward;
+pub mod open_schedule_bucket;
+pub mod close_schedule_bucket;
+pub mod update_approved_validators;
+pub mod update_stake_pool;
+pub mod update_lending_reserve;
+pub mod lend_escrow;
+pub mod withdraw_lent_escrow;
+pub mod update_yield_split;
+pub mod distribute_yield;
+pub mod settle_refund_stake;
+pub mod settle_refund_lending;
+pub mod settle_refund_stake_pool;
+pub mod finalize_cancellation;
+pub mod create_plan;
+pub mod change_plan;
+pub mod update_plan_price;
+pub mod accept_price_change;
+pub mod set_spending_cap;
+pub mod initialize_delegated_subscription;
+pub mod initialize_stream;
+pub mod withdraw_stream;
+pub mod stop_stream;
+pub mod report_usage;
+pub mod attach_add_on;
+pub mod detach_add_on;
+pub mod create_coupon;
+pub mod apply_coupon;
+pub mod register_referrer;
+pub mod update_referral_program;
+pub mod set_payout_split;
+pub mod update_settlement_delay;
+pub mod open_settlement;
+pub mod claim_settlement;
+pub mod release_held_payment;
+pub mod open_dispute;
+pub mod appoint_arbiter;
+pub mod submit_evidence_uri;
+pub mod resolve_dispute;
 
+// Every instruction module exports its own `handler`; lib.rs calls them by path
+#[allow(ambiguous_glob_reexports)]
 pub use initialize_subscription::*;
 pub use process_payment::*;
+pub use process_payments_batch::*;
 pub use cancel_subscription::*;
-pub use update_fee_wallet::*;
+pub use update_platform_fee_wallet::*;
 pub use stake_escrow::*;
+pub use deactivate_stake::*;
+pub use withdraw_stake::*;
+pub use deposit_stake_pool::*;
+pub use redeem_stake_pool_sol::*;
+pub use redeem_stake_pool_stake::*;
+pub use init_platform_config::*;
+pub use update_gas_reimbursement::*;
+pub use update_keeper_reward::*;
+pub use open_schedule_bucket::*;
+pub use close_schedule_bucket::*;
+pub use update_approved_validators::*;
+pub use update_stake_pool::*;
+pub use update_lending_reserve::*;
+pub use lend_escrow::*;
+pub use withdraw_lent_escrow::*;
+pub use update_yield_split::*;
+pub use distribute_yield::*;
+pub use settle_refund_stake::*;
+pub use settle_refund_lending::*;
+pub use settle_refund_stake_pool::*;
+pub use finalize_cancellation::*;
+pub use create_plan::*;
+pub use change_plan::*;
+pub use update_plan_price::*;
+pub use accept_price_change::*;
+pub use set_spending_cap::*;
+pub use initialize_delegated_subscription::*;
+pub use initialize_stream::*;
+pub use withdraw_stream::*;
+pub use stop_stream::*;
+pub use report_usage::*;
+pub use attach_add_on::*;
+pub use detach_add_on::*;
+pub use create_coupon::*;
+pub use apply_coupon::*;
+pub use register_referrer::*;
+pub use update_referral_program::*;
+pub use set_payout_split::*;
+pub use update_settlement_delay::*;
+pub use open_settlement::*;
+pub use claim_settlement::*;
+pub use release_held_payment::*;
+pub use open_dispute::*;
+pub use appoint_arbiter::*;
+pub use submit_evidence_uri::*;
+pub use resolve_dispute::*;
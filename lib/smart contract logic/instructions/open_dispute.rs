@@ -0,0 +1,101 @@
+use anchor_lang::prelude::*;
+use crate::state::{Subscription, PlatformConfig, HeldPayment, Dispute, DisputeStatus};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+#[instruction(due_at: i64)]
+pub struct OpenDispute<'info> {
+    #[account(has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    // Pays rent for the dispute record
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"held",
+            subscription.recipient_token_account.as_ref(),
+            subscription.key().as_ref(),
+            due_at.to_le_bytes().as_ref()
+        ],
+        bump = held_payment.bump
+    )]
+    pub held_payment: Account<'info, HeldPayment>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Dispute::LEN,
+        seeds = [b"dispute", subscription.key().as_ref(), due_at.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub dispute: Account<'info, Dispute>,
+
+    // A dispute freezes the payment until the arbiter resolves it, so one must be appointed
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        constraint = platform_config.arbiter != Pubkey::default() @ SubscriptionError::ArbiterRequired
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Freeze a held payment, identified by the due date of the period it paid
+/// for, so the merchant can't claim it until the arbiter resolves the dispute
+pub fn handler(ctx: Context<OpenDispute>, due_at: i64, evidence_uri: String) -> Result<()> {
+    require!(
+        evidence_uri.len() <= Dispute::MAX_URI_LEN,
+        SubscriptionError::EvidenceUriTooLong
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    let user = ctx.accounts.user.key();
+    let payment = &mut ctx.accounts.held_payment;
+    let recipient_token_account = payment.recipient_token_account;
+    require!(!payment.disputed, SubscriptionError::InvalidSubscriptionState);
+    require!(now < payment.release_at, SubscriptionError::DisputeWindowClosed);
+    payment.disputed = true;
+    let amount = payment.amount;
+
+    ctx.accounts.dispute.set_inner(Dispute {
+        subscription: ctx.accounts.subscription.key(),
+        user,
+        recipient_token_account,
+        due_at,
+        amount,
+        status: DisputeStatus::Open,
+        user_evidence_uri: evidence_uri.clone(),
+        merchant_evidence_uri: String::new(),
+        refunded: 0,
+        opened_at: now,
+        resolved_at: None,
+        bump: ctx.bumps.dispute,
+    });
+
+    emit!(DisputeOpened {
+        dispute: ctx.accounts.dispute.key(),
+        user,
+        recipient_token_account,
+        due_at,
+        amount,
+        evidence_uri,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct DisputeOpened {
+    pub dispute: Pubkey,
+    pub user: Pubkey,
+    pub recipient_token_account: Pubkey,
+    pub due_at: i64,
+    pub amount: u64,
+    pub evidence_uri: String,
+    pub timestamp: i64,
+}
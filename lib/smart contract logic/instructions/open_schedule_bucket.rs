@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+use crate::state::ScheduleBucket;
+
+// Anyone may open a schedule page; whoever first files a subscription under a new day pays its rent,
+// and gets it back through close_schedule_bucket once the day has passed and the page is empty
+#[derive(Accounts)]
+#[instruction(day: i64, page: u16)]
+pub struct OpenScheduleBucket<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ScheduleBucket::LEN,
+        seeds = [b"schedule", day.to_le_bytes().as_ref(), page.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<OpenScheduleBucket>,
+    day: i64,
+    page: u16,
+) -> Result<()> {
+    let bump = ctx.bumps.schedule_bucket;
+
+    ctx.accounts.schedule_bucket.set_inner(ScheduleBucket {
+        day,
+        page,
+        bump,
+        payer: ctx.accounts.payer.key(),
+        subscriptions: Vec::new(),
+    });
+
+    Ok(())
+}
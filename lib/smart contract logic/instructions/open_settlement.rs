@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::Settlement;
+
+// Anyone may open a merchant's settlement; whoever first charges into it
+// while settlements are held pays its rent
+#[derive(Accounts)]
+pub struct OpenSettlement<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Settlement::LEN,
+        seeds = [b"settlement", recipient_token_account.key().as_ref()],
+        bump
+    )]
+    pub settlement: Account<'info, Settlement>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"settlement-vault", recipient_token_account.key().as_ref()],
+        bump,
+        token::mint = usdc_mint,
+        token::authority = settlement
+    )]
+    pub settlement_vault: Account<'info, TokenAccount>,
+
+    #[account(token::mint = usdc_mint)]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub usdc_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+pub fn handler(ctx: Context<OpenSettlement>) -> Result<()> {
+    let bump = ctx.bumps.settlement;
+
+    ctx.accounts.settlement.set_inner(Settlement {
+        recipient_token_account: ctx.accounts.recipient_token_account.key(),
+        released: 0,
+        payouts: Vec::new(),
+        held: 0,
+        bump,
+    });
+
+    Ok(())
+}
@@ -1,33 +1,45 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
-use crate::state::{Subscription, PlatformConfig};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{Token, TokenAccount, Mint};
+use crate::state::{Subscription, PlatformConfig, ScheduleBucket, Plan, Referrer, Settlement, HeldPayment};
 use crate::error::SubscriptionError;
-
-const FEE_AMOUNT_USDC: u64 = 10_000; // 0.10 USDC with 6 decimals
+use crate::billing::AddOnCharge;
+use crate::instructions::stake_escrow::create_pda;
+use crate::{billing, oracle};
 
 #[derive(Accounts)]
 pub struct ProcessPayment<'info> {
-    #[account(mut)]
+    #[account(mut, has_one = user)]
     pub subscription: Account<'info, Subscription>,
 
     #[account(
         mut,
         seeds = [b"subscription", subscription.user.as_ref()],
-        bump = subscription.bump,
-        has_one = user
+        bump = subscription.bump
     )]
+    /// CHECK: This is the PDA signer of the subscription
     pub subscription_signer: AccountInfo<'info>,
 
     /// CHECK: Just storing and verifying keys
     pub user: AccountInfo<'info>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
     pub escrow_token_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized
+    )]
     pub recipient_token_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        constraint = fee_wallet_token_account.owner == platform_config.fee_wallet @ SubscriptionError::Unauthorized
+    )]
     pub fee_wallet_token_account: Account<'info, TokenAccount>,
 
     #[account(
@@ -36,66 +48,300 @@ pub struct ProcessPayment<'info> {
     )]
     pub platform_config: Account<'info, PlatformConfig>,
 
+    #[account(
+        mut,
+        constraint = schedule_bucket.holds(&subscription) @ SubscriptionError::ScheduleMismatch
+    )]
+    pub schedule_bucket: Account<'info, ScheduleBucket>,
+
+    // Any open page for the day the following payment falls due
+    #[account(
+        mut,
+        constraint = next_schedule_bucket.day == billing::next_schedule_day(&subscription) @ SubscriptionError::ScheduleMismatch
+    )]
+    pub next_schedule_bucket: Account<'info, ScheduleBucket>,
+
+    /// CHECK: Read through the oracle adapter, address pinned by the platform config
+    #[account(address = platform_config.price_oracle)]
+    pub price_oracle: UncheckedAccount<'info>,
+
+    // Any keeper may submit a due charge; it is repaid for gas and earns a share of the fee
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        token::authority = keeper
+    )]
+    pub keeper_token_account: Account<'info, TokenAccount>,
+
     pub usdc_mint: Account<'info, Mint>,
 
+    // Required for plan subscriptions so merchant price changes apply
+    #[account(
+        constraint = subscription.plan == Some(plan.key()) @ SubscriptionError::PlanRequired
+    )]
+    pub plan: Option<Account<'info, Plan>>,
+
+    // Required while a referred subscription still owes its referrer a share
+    #[account(
+        mut,
+        constraint = subscription.referrer == Some(referrer.wallet) @ SubscriptionError::ReferrerRequired
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+
+    #[account(
+        mut,
+        token::mint = usdc_mint
+    )]
+    pub referrer_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// CHECK: The merchant's payout split PDA, read by billing::load_payout_split.
+    /// When a split is set its payee token accounts follow as remaining accounts.
+    #[account(
+        seeds = [b"payout-split", subscription.recipient_token_account.as_ref()],
+        bump
+    )]
+    pub payout_split: UncheckedAccount<'info>,
+
+    // Required while the platform holds settlements; the merchant's part of
+    // the charge is paid into the vault and held instead
+    #[account(
+        mut,
+        seeds = [b"settlement", subscription.recipient_token_account.as_ref()],
+        bump = settlement.bump
+    )]
+    pub settlement: Option<Account<'info, Settlement>>,
+
+    #[account(
+        mut,
+        seeds = [b"settlement-vault", subscription.recipient_token_account.as_ref()],
+        bump
+    )]
+    pub settlement_vault: Option<Account<'info, TokenAccount>>,
+
+    /// CHECK: Records the held charge. Created by the handler only once the
+    /// charge has gone through, so cranks that end without charging leave
+    /// nothing behind; the keeper pays its rent and gets it back on release.
+    #[account(
+        mut,
+        seeds = [
+            b"held",
+            subscription.recipient_token_account.as_ref(),
+            subscription.key().as_ref(),
+            subscription.next_payment_due.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub held_payment: Option<UncheckedAccount<'info>>,
+
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<ProcessPayment>) -> Result<()> {
-    let subscription = &ctx.accounts.subscription;
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, ProcessPayment<'info>>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    // Reject inactive, early and duplicate cranks before doing any transfers
+    billing::check_due(&ctx.accounts.subscription, now)?;
 
-    if !subscription.is_active {
-        return Err(SubscriptionError::SubscriptionInactive.into());
+    match &ctx.accounts.plan {
+        Some(plan) => {
+            let subscription = &mut ctx.accounts.subscription;
+            let refund_destination = get_associated_token_address(&subscription.user, &ctx.accounts.usdc_mint.key());
+            if !billing::sync_plan_price(subscription, plan, refund_destination) {
+                // Ending instead of charging is a successful crank, so the new state sticks
+                emit!(PriceChangeDeclined {
+                    user: subscription.user,
+                    plan: plan.key(),
+                    price: plan.price_at(subscription.next_payment_due),
+                    cancel_at: subscription.next_payment_due,
+                    timestamp: now,
+                });
+                return Ok(());
+            }
+        }
+        None => require!(
+            ctx.accounts.subscription.plan.is_none(),
+            SubscriptionError::PlanRequired
+        ),
     }
 
-    let total_required = subscription.monthly_amount + FEE_AMOUNT_USDC;
-    let escrow_balance = ctx.accounts.escrow_token_account.amount;
+    // Price the keeper's SOL gas cost in USDC
+    let sol_price = oracle::read_sol_price(&ctx.accounts.price_oracle.to_account_info())?;
+    let gas_reimbursement = oracle::lamports_to_usdc(
+        ctx.accounts.platform_config.gas_reimbursement_lamports,
+        sol_price,
+    )?;
+
+    let charge = billing::compute_charge(
+        &ctx.accounts.subscription,
+        &ctx.accounts.platform_config,
+        gas_reimbursement,
+        now,
+    )?;
+    let period_total = charge.period_total()?;
+    require!(
+        billing::within_spending_cap(&ctx.accounts.subscription, period_total, period_total),
+        SubscriptionError::SpendingCapExceeded
+    );
+
+    let escrow_balance = billing::available_funds(&ctx.accounts.subscription, &ctx.accounts.escrow_token_account);
+
+    // A delegated charge the user's wallet can't cover leaves the period
+    // unpaid; the next crank retries it once the allowance is topped up
+    let total = charge.total()?;
+    if ctx.accounts.subscription.delegated && escrow_balance < total {
+        let subscription = &mut ctx.accounts.subscription;
+        subscription.past_due = true;
+
+        emit!(PaymentPastDue {
+            user: subscription.user,
+            amount_due: total,
+            available: escrow_balance,
+            timestamp: now,
+        });
+        return Ok(());
+    }
 
     require!(
-        escrow_balance >= total_required,
+        escrow_balance >= charge.base_total()?,
         SubscriptionError::InsufficientFunds
     );
+    require!(
+        escrow_balance >= total,
+        SubscriptionError::InsufficientGasFeeFunds
+    );
 
-    let seeds = &[
-        b"subscription",
-        subscription.user.as_ref(),
-        &[subscription.bump],
-    ];
-    let signer = &[&seeds[..]];
-
-    // Step 1: Transfer main payment to recipient
-    token::transfer(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.recipient_token_account.to_account_info(),
-                authority: ctx.accounts.subscription_signer.clone(),
-            },
-            signer,
-        ),
-        subscription.monthly_amount,
+    let referrer_token_account = match (&ctx.accounts.referrer, &ctx.accounts.referrer_token_account) {
+        (Some(referrer), Some(token_account)) => {
+            require_keys_eq!(token_account.owner, referrer.wallet, SubscriptionError::Unauthorized);
+            Some(token_account.to_account_info())
+        }
+        _ => None,
+    };
+
+    // Held charges record how they split between payees and are paid out on claim
+    let settlement_delay = ctx.accounts.platform_config.settlement_delay;
+    let (recipient_token_account, payout_split) = if settlement_delay > 0 {
+        let settlement_vault = ctx
+            .accounts
+            .settlement_vault
+            .as_ref()
+            .filter(|_| ctx.accounts.settlement.is_some() && ctx.accounts.held_payment.is_some())
+            .ok_or(SubscriptionError::SettlementRequired)?;
+        (settlement_vault.to_account_info(), None)
+    } else {
+        let payout_split = billing::load_payout_split(
+            &ctx.accounts.payout_split.to_account_info(),
+            &ctx.accounts.recipient_token_account.to_account_info(),
+            ctx.remaining_accounts,
+        )?;
+        (ctx.accounts.recipient_token_account.to_account_info(), payout_split)
+    };
+
+    billing::transfer_charge(
+        &ctx.accounts.subscription,
+        &charge,
+        &billing::ChargeAccounts {
+            escrow_token_account: ctx.accounts.escrow_token_account.to_account_info(),
+            recipient_token_account,
+            fee_wallet_token_account: ctx.accounts.fee_wallet_token_account.to_account_info(),
+            keeper_token_account: ctx.accounts.keeper_token_account.to_account_info(),
+            subscription_signer: ctx.accounts.subscription_signer.clone(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            referrer_token_account,
+            payout_split,
+        },
     )?;
 
-    // Step 2: Transfer $0.10 USDC fee to platform
-    token::transfer(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.escrow_token_account.to_account_info(),
-                to: ctx.accounts.fee_wallet_token_account.to_account_info(),
-                authority: ctx.accounts.subscription_signer.clone(),
-            },
-            signer,
-        ),
-        FEE_AMOUNT_USDC,
+    if let Some(referrer) = ctx.accounts.referrer.as_mut().filter(|_| charge.referral_fee > 0) {
+        referrer.periods_paid += 1;
+        referrer.total_earned = billing::checked_sum([referrer.total_earned, charge.referral_fee])?;
+
+        emit!(ReferralPaid {
+            referrer: referrer.wallet,
+            user: ctx.accounts.subscription.user,
+            amount: charge.referral_fee,
+            periods_paid: ctx.accounts.subscription.referral_periods_paid + 1,
+            timestamp: now,
+        });
+    }
+
+    if let (Some(settlement), Some(held_payment)) = (
+        ctx.accounts.settlement.as_mut().filter(|_| settlement_delay > 0),
+        ctx.accounts.held_payment.as_ref(),
+    ) {
+        let release_at = now + settlement_delay;
+        let amount = charge.merchant_payout()?;
+        settlement.held = billing::checked_sum([settlement.held, amount])?;
+
+        let subscription_key = ctx.accounts.subscription.key();
+        let due_at = ctx.accounts.subscription.next_payment_due.to_le_bytes();
+        let bump = ctx.bumps.held_payment;
+        let seeds: &[&[u8]] = &[
+            b"held",
+            settlement.recipient_token_account.as_ref(),
+            subscription_key.as_ref(),
+            due_at.as_ref(),
+            &[bump],
+        ];
+        let space = 8 + HeldPayment::LEN;
+        create_pda(
+            &ctx.accounts.keeper.to_account_info(),
+            &held_payment.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            Rent::get()?.minimum_balance(space),
+            space,
+            &crate::ID,
+            seeds,
+        )?;
+        HeldPayment {
+            recipient_token_account: settlement.recipient_token_account,
+            subscription: subscription_key,
+            user: ctx.accounts.subscription.user,
+            due_at: ctx.accounts.subscription.next_payment_due,
+            amount,
+            release_at,
+            payouts: billing::held_payouts(&ctx.accounts.payout_split, settlement.recipient_token_account, amount)?,
+            disputed: false,
+            payer: ctx.accounts.keeper.key(),
+            bump,
+        }
+        .try_serialize(&mut &mut held_payment.try_borrow_mut_data()?[..])?;
+
+        emit!(PaymentHeld {
+            user: ctx.accounts.subscription.user,
+            recipient_token_account: settlement.recipient_token_account,
+            due_at: ctx.accounts.subscription.next_payment_due,
+            amount,
+            release_at,
+            timestamp: now,
+        });
+    }
+
+    let subscription = &mut ctx.accounts.subscription;
+    billing::record_payment(
+        subscription,
+        &charge,
+        now,
+        &mut ctx.accounts.schedule_bucket,
+        &mut ctx.accounts.next_schedule_bucket,
     )?;
 
     emit!(PaymentProcessed {
         user: subscription.user,
-        amount: subscription.monthly_amount,
+        amount: charge.amount,
+        discount: charge.discount,
+        usage_amount: charge.usage_amount,
+        add_ons: charge.add_ons,
         fee_wallet: ctx.accounts.platform_config.fee_wallet,
-        timestamp: Clock::get()?.unix_timestamp,
+        keeper: ctx.accounts.keeper.key(),
+        keeper_reward: charge.keeper_reward,
+        gas_reimbursement: charge.gas_reimbursement,
+        next_payment_due: subscription.next_payment_due,
+        timestamp: now,
     });
 
     Ok(())
@@ -105,6 +351,54 @@ pub fn handler(ctx: Context<ProcessPayment>) -> Result<()> {
 pub struct PaymentProcessed {
     pub user: Pubkey,
     pub amount: u64,
+    // Coupon discount already taken off amount
+    pub discount: u64,
+    // Metered plans: usage billed on top of amount
+    pub usage_amount: u64,
+    // Add-ons billed on top of amount, itemized
+    pub add_ons: Vec<AddOnCharge>,
     pub fee_wallet: Pubkey,
+    pub keeper: Pubkey,
+    pub keeper_reward: u64,
+    pub gas_reimbursement: u64,
+    pub next_payment_due: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralPaid {
+    pub referrer: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    // Charges of this subscription that have paid the referrer, including this one
+    pub periods_paid: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PaymentHeld {
+    pub user: Pubkey,
+    pub recipient_token_account: Pubkey,
+    // Due date of the period paid for, which identifies the payment in disputes
+    pub due_at: i64,
+    pub amount: u64,
+    pub release_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PaymentPastDue {
+    pub user: Pubkey,
+    pub amount_due: u64,
+    pub available: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PriceChangeDeclined {
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub price: u64,
+    pub cancel_at: i64,
     pub timestamp: i64,
 }
@@ -0,0 +1,338 @@
+use std::collections::BTreeSet;
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::{Token, TokenAccount, Mint};
+use crate::state::{Subscription, PlatformConfig, ScheduleBucket, Plan};
+use crate::error::SubscriptionError;
+use crate::instructions::process_payment::{PaymentProcessed, PaymentPastDue, PriceChangeDeclined};
+use crate::{billing, oracle};
+
+/// Each entry in `remaining_accounts` is [subscription, escrow, recipient, plan,
+/// payout split], with this program's id in the plan slot for subscriptions
+/// without a plan. The payout split slot is the recipient's split PDA; entries
+/// whose merchant has a split set are left to process_payment.
+/// All entries are charged out of `due_bucket` and refiled into `next_bucket`;
+/// entries filed elsewhere are skipped so one bucket can be drained per batch.
+pub const ACCOUNTS_PER_ENTRY: usize = 5;
+
+/// Largest batch a single transaction may carry. 10 entries is 50 remaining
+/// accounts, which with the fixed accounts stays under the 64 account lock
+/// limit when sent through an address lookup table. Each charge costs roughly
+/// 20k compute units (three token CPIs plus deserialization), so a full batch
+/// needs a compute budget request of about 250k units.
+pub const MAX_BATCH_SIZE: usize = 10;
+
+#[derive(Accounts)]
+pub struct ProcessPaymentsBatch<'info> {
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        constraint = fee_wallet_token_account.owner == platform_config.fee_wallet @ SubscriptionError::Unauthorized
+    )]
+    pub fee_wallet_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    #[account(mut)]
+    pub due_bucket: Account<'info, ScheduleBucket>,
+
+    #[account(mut)]
+    pub next_bucket: Account<'info, ScheduleBucket>,
+
+    /// CHECK: Read through the oracle adapter, address pinned by the platform config
+    #[account(address = platform_config.price_oracle)]
+    pub price_oracle: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        token::authority = keeper
+    )]
+    pub keeper_token_account: Account<'info, TokenAccount>,
+
+    pub usdc_mint: Account<'info, Mint>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// A due entry that passed every check except paying its share of the batch's gas
+struct PendingCharge<'info> {
+    subscription: Account<'info, Subscription>,
+    subscription_info: AccountInfo<'info>,
+    escrow_info: AccountInfo<'info>,
+    recipient_info: AccountInfo<'info>,
+    available: u64,
+    charge: billing::Charge,
+}
+
+impl PendingCharge<'_> {
+    /// Why the entry can't be charged once it also repays `gas_share`
+    fn shortfall(&self, gas_share: u64) -> Result<Option<SkipReason>> {
+        let period_total = billing::checked_sum([self.charge.period_total()?, gas_share])?;
+        Ok(if !billing::within_spending_cap(&self.subscription, period_total, period_total) {
+            Some(SkipReason::OverSpendingCap)
+        } else if self.available < billing::checked_sum([self.charge.total()?, gas_share])? {
+            Some(SkipReason::Underfunded)
+        } else {
+            None
+        })
+    }
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, 'info, 'info, ProcessPaymentsBatch<'info>>) -> Result<()> {
+    let entries = ctx.remaining_accounts;
+    require!(
+        !entries.is_empty() && entries.len() % ACCOUNTS_PER_ENTRY == 0,
+        SubscriptionError::InvalidBatch
+    );
+
+    let batch_size = entries.len() / ACCOUNTS_PER_ENTRY;
+    require!(batch_size <= MAX_BATCH_SIZE, SubscriptionError::InvalidBatch);
+
+    let now = Clock::get()?.unix_timestamp;
+
+    // First pass: price every entry without gas and skip the ones that can't be charged
+    let mut pending: Vec<PendingCharge<'info>> = Vec::with_capacity(batch_size);
+    let mut seen = BTreeSet::new();
+    for entry in entries.chunks(ACCOUNTS_PER_ENTRY) {
+        let subscription_info = &entry[0];
+        let escrow_info = &entry[1];
+        let recipient_info = &entry[2];
+        let plan_info = &entry[3];
+        let payout_split_info = &entry[4];
+
+        // Mismatched accounts are a keeper bug, so they fail the whole batch.
+        // A repeated entry would be priced twice against the same escrow
+        // balance and charged twice in one period.
+        require!(seen.insert(subscription_info.key()), SubscriptionError::InvalidBatch);
+        let mut subscription = Account::<Subscription>::try_from(subscription_info)?;
+        require_keys_eq!(
+            escrow_info.key(),
+            subscription.escrow_token_account,
+            SubscriptionError::EscrowAccountNotFound
+        );
+        require_keys_eq!(
+            recipient_info.key(),
+            subscription.recipient_token_account,
+            SubscriptionError::Unauthorized
+        );
+
+        let (payout_split_key, _) = Pubkey::find_program_address(
+            &[b"payout-split", subscription.recipient_token_account.as_ref()],
+            ctx.program_id,
+        );
+        require_keys_eq!(payout_split_info.key(), payout_split_key, SubscriptionError::PayeeMismatch);
+        let has_payout_split = payout_split_info.owner == ctx.program_id && !payout_split_info.data_is_empty();
+
+        let plan = if plan_info.key() == crate::ID {
+            None
+        } else {
+            Some(Account::<Plan>::try_from(plan_info)?)
+        };
+        require!(
+            subscription.plan == plan.as_ref().map(|plan| plan.key()),
+            SubscriptionError::PlanRequired
+        );
+
+        // Apply merchant price changes to due subscriptions before pricing the charge
+        if let Some(plan) = &plan {
+            let due = subscription.is_active
+                && subscription.cancel_at.is_none()
+                && now >= subscription.next_payment_due;
+            let refund_destination = get_associated_token_address(&subscription.user, &ctx.accounts.usdc_mint.key());
+            if due && !billing::sync_plan_price(&mut subscription, plan, refund_destination) {
+                subscription.exit(ctx.program_id)?;
+                emit!(PriceChangeDeclined {
+                    user: subscription.user,
+                    plan: plan.key(),
+                    price: plan.price_at(subscription.next_payment_due),
+                    cancel_at: subscription.next_payment_due,
+                    timestamp: now,
+                });
+                continue;
+            }
+        }
+
+        let escrow = Account::<TokenAccount>::try_from(escrow_info)?;
+        let available = billing::available_funds(&subscription, &escrow);
+        let charge = billing::compute_charge(
+            &subscription,
+            &ctx.accounts.platform_config,
+            0,
+            now,
+        )?;
+
+        // Subscriptions that can't be charged are reported and skipped
+        let skip_reason = if !subscription.is_active {
+            Some(SkipReason::Inactive)
+        } else if subscription.cancel_at.is_some() {
+            Some(SkipReason::Ending)
+        } else if now < subscription.next_payment_due {
+            Some(SkipReason::NotDue)
+        } else if !billing::within_spending_cap(&subscription, charge.period_total()?, charge.period_total()?) {
+            Some(SkipReason::OverSpendingCap)
+        } else if charge.referral_fee > 0 {
+            Some(SkipReason::ReferralDue)
+        } else if has_payout_split {
+            Some(SkipReason::SplitPayout)
+        } else if ctx.accounts.platform_config.settlement_delay > 0 {
+            Some(SkipReason::SettlementHeld)
+        } else if available < charge.total()? {
+            Some(SkipReason::Underfunded)
+        } else if !ctx.accounts.due_bucket.holds(&subscription)
+            || ctx.accounts.next_bucket.day != billing::next_schedule_day(&subscription)
+        {
+            Some(SkipReason::ScheduleMismatch)
+        } else if ctx.accounts.next_bucket.subscriptions.len() + pending.len() >= ScheduleBucket::MAX_SUBSCRIPTIONS {
+            Some(SkipReason::ScheduleFull)
+        } else {
+            None
+        };
+
+        match skip_reason {
+            Some(reason) => skip(&mut subscription, reason, charge.total()?, available, now, ctx.program_id)?,
+            None => pending.push(PendingCharge {
+                subscription,
+                subscription_info: subscription_info.clone(),
+                escrow_info: escrow_info.clone(),
+                recipient_info: recipient_info.clone(),
+                available,
+                charge,
+            }),
+        }
+    }
+
+    // One transaction fee covers the whole batch, so each charged entry repays
+    // an equal share. Entries that can't also cover their share drop out, which
+    // raises everyone else's, until the set of charged entries settles.
+    let sol_price = oracle::read_sol_price(&ctx.accounts.price_oracle.to_account_info())?;
+    let batch_gas = oracle::lamports_to_usdc(
+        ctx.accounts.platform_config.gas_reimbursement_lamports,
+        sol_price,
+    )?;
+    let gas_share = loop {
+        let gas_share = if pending.is_empty() { 0 } else { batch_gas / pending.len() as u64 };
+        let charged = pending.len();
+        let mut index = 0;
+        while index < pending.len() {
+            match pending[index].shortfall(gas_share)? {
+                Some(reason) => {
+                    let mut entry = pending.remove(index);
+                    let amount_due = billing::checked_sum([entry.charge.total()?, gas_share])?;
+                    skip(&mut entry.subscription, reason, amount_due, entry.available, now, ctx.program_id)?;
+                }
+                None => index += 1,
+            }
+        }
+        if pending.len() == charged {
+            break gas_share;
+        }
+    };
+
+    // Second pass: charge what's left
+    for PendingCharge { mut subscription, subscription_info, escrow_info, recipient_info, mut charge, .. } in pending {
+        charge.gas_reimbursement = gas_share;
+
+        billing::transfer_charge(
+            &subscription,
+            &charge,
+            &billing::ChargeAccounts {
+                escrow_token_account: escrow_info,
+                recipient_token_account: recipient_info,
+                fee_wallet_token_account: ctx.accounts.fee_wallet_token_account.to_account_info(),
+                keeper_token_account: ctx.accounts.keeper_token_account.to_account_info(),
+                subscription_signer: subscription_info,
+                token_program: ctx.accounts.token_program.to_account_info(),
+                referrer_token_account: None,
+                payout_split: None,
+            },
+        )?;
+
+        billing::record_payment(
+            &mut subscription,
+            &charge,
+            now,
+            &mut ctx.accounts.due_bucket,
+            &mut ctx.accounts.next_bucket,
+        )?;
+        subscription.exit(ctx.program_id)?;
+
+        emit!(PaymentProcessed {
+            user: subscription.user,
+            amount: charge.amount,
+            discount: charge.discount,
+            usage_amount: charge.usage_amount,
+            add_ons: charge.add_ons,
+            fee_wallet: ctx.accounts.platform_config.fee_wallet,
+            keeper: ctx.accounts.keeper.key(),
+            keeper_reward: charge.keeper_reward,
+            gas_reimbursement: charge.gas_reimbursement,
+            next_payment_due: subscription.next_payment_due,
+            timestamp: now,
+        });
+    }
+
+    Ok(())
+}
+
+/// Report a skipped entry. An underfunded delegated subscription is also
+/// marked past due, since its user has to raise the allowance.
+fn skip(
+    subscription: &mut Account<Subscription>,
+    reason: SkipReason,
+    amount_due: u64,
+    available: u64,
+    now: i64,
+    program_id: &Pubkey,
+) -> Result<()> {
+    if reason == SkipReason::Underfunded && subscription.delegated {
+        subscription.past_due = true;
+        subscription.exit(program_id)?;
+
+        emit!(PaymentPastDue {
+            user: subscription.user,
+            amount_due,
+            available,
+            timestamp: now,
+        });
+    }
+
+    emit!(PaymentSkipped {
+        user: subscription.user,
+        reason,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Inactive,
+    Ending,
+    NotDue,
+    OverSpendingCap,
+    // Batches don't carry referrer accounts; process_payment charges these
+    ReferralDue,
+    // Likewise for payee accounts of a merchant's payout split
+    SplitPayout,
+    // And for merchant settlement accounts while payments are held
+    SettlementHeld,
+    Underfunded,
+    ScheduleMismatch,
+    ScheduleFull,
+}
+
+#[event]
+pub struct PaymentSkipped {
+    pub user: Pubkey,
+    pub reason: SkipReason,
+    pub timestamp: i64,
+}
@@ -0,0 +1,190 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{Subscription, PlatformConfig};
+use crate::error::SubscriptionError;
+use crate::stake_pool::{self, STAKE_POOL_PROGRAM_ID};
+
+#[derive(Accounts)]
+pub struct RedeemStakePoolSol<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    // Receives the redeemed SOL
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    /// CHECK: Parsed by the stake pool adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.approved_stake_pool @ SubscriptionError::InvalidStakePool
+    )]
+    pub stake_pool: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    pub pool_withdraw_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub reserve_stake: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub manager_fee_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool-tokens", subscription.key().as_ref()],
+        bump
+    )]
+    pub pool_token_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Stake history sysvar required by the reserve withdrawal
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = anchor_lang::solana_program::stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    /// CHECK: SPL stake pool program
+    #[account(address = STAKE_POOL_PROGRAM_ID)]
+    pub stake_pool_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Instant redemption out of the pool's SOL reserve to the user's wallet, for
+/// when they need the SOL now. Limited by what the reserve holds. Cancels
+/// redeem the same way through settle_refund_stake_pool.
+pub fn handler(ctx: Context<RedeemStakePoolSol>, pool_tokens: u64) -> Result<()> {
+    require!(
+        pool_tokens > 0 && pool_tokens <= ctx.accounts.pool_token_vault.amount,
+        SubscriptionError::InvalidAmount
+    );
+
+    let lamports = withdraw_pool_sol(
+        &mut ctx.accounts.subscription,
+        &mut ctx.accounts.pool_token_vault,
+        &PoolSolAccounts {
+            stake_pool: ctx.accounts.stake_pool.to_account_info(),
+            pool_withdraw_authority: ctx.accounts.pool_withdraw_authority.to_account_info(),
+            subscription_signer: ctx.accounts.subscription_signer.clone(),
+            reserve_stake: ctx.accounts.reserve_stake.to_account_info(),
+            lamports_to: ctx.accounts.user.to_account_info(),
+            manager_fee_account: ctx.accounts.manager_fee_account.to_account_info(),
+            pool_mint: ctx.accounts.pool_mint.to_account_info(),
+            clock: ctx.accounts.clock.to_account_info(),
+            stake_history: ctx.accounts.stake_history.to_account_info(),
+            stake_program: ctx.accounts.stake_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            stake_pool_program: ctx.accounts.stake_pool_program.to_account_info(),
+        },
+        pool_tokens,
+    )?;
+
+    emit!(StakePoolRedeemed {
+        user: ctx.accounts.subscription.user,
+        pool_tokens,
+        lamports,
+        delayed: false,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Accounts the stake pool's WithdrawSol needs besides the pool token vault
+pub(crate) struct PoolSolAccounts<'info> {
+    pub stake_pool: AccountInfo<'info>,
+    pub pool_withdraw_authority: AccountInfo<'info>,
+    pub subscription_signer: AccountInfo<'info>,
+    pub reserve_stake: AccountInfo<'info>,
+    pub lamports_to: AccountInfo<'info>,
+    pub manager_fee_account: AccountInfo<'info>,
+    pub pool_mint: AccountInfo<'info>,
+    pub clock: AccountInfo<'info>,
+    pub stake_history: AccountInfo<'info>,
+    pub stake_program: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub stake_pool_program: AccountInfo<'info>,
+}
+
+/// Burn `pool_tokens` from the subscription's vault for SOL out of the pool's
+/// reserve, sent to `lamports_to`, then refresh the subscription's pool token
+/// balance and exchange rate. Returns the lamports received.
+pub(crate) fn withdraw_pool_sol<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    pool_token_vault: &mut Account<'info, TokenAccount>,
+    accounts: &PoolSolAccounts<'info>,
+    pool_tokens: u64,
+) -> Result<u64> {
+    let pool = stake_pool::read_pool(&accounts.stake_pool)?;
+    let withdraw_ix = stake_pool::withdraw_sol(
+        accounts.stake_pool.key,
+        &pool,
+        accounts.subscription_signer.key,
+        &pool_token_vault.key(),
+        accounts.lamports_to.key,
+        pool_tokens,
+    );
+
+    let lamports_before = accounts.lamports_to.lamports();
+    anchor_lang::solana_program::program::invoke_signed(
+        &withdraw_ix,
+        &[
+            accounts.stake_pool.clone(),
+            accounts.pool_withdraw_authority.clone(),
+            accounts.subscription_signer.clone(),
+            pool_token_vault.to_account_info(),
+            accounts.reserve_stake.clone(),
+            accounts.lamports_to.clone(),
+            accounts.manager_fee_account.clone(),
+            accounts.pool_mint.clone(),
+            accounts.clock.clone(),
+            accounts.stake_history.clone(),
+            accounts.stake_program.clone(),
+            accounts.token_program.clone(),
+            accounts.stake_pool_program.clone(),
+        ],
+        &[&[
+            b"subscription",
+            subscription.user.as_ref(),
+            &[subscription.bump],
+        ][..]],
+    )?;
+    let lamports = accounts.lamports_to.lamports() - lamports_before;
+
+    pool_token_vault.reload()?;
+    let pool = stake_pool::read_pool(&accounts.stake_pool)?;
+    subscription.pool_token_balance = pool_token_vault.amount;
+    subscription.pool_exchange_rate = pool.exchange_rate();
+
+    Ok(lamports)
+}
+
+#[event]
+pub struct StakePoolRedeemed {
+    pub user: Pubkey,
+    pub pool_tokens: u64,
+    pub lamports: u64,
+    pub delayed: bool,
+    pub timestamp: i64,
+}
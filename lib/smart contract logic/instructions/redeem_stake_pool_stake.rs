@@ -0,0 +1,176 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{Subscription, PlatformConfig};
+use crate::error::SubscriptionError;
+use crate::instructions::redeem_stake_pool_sol::StakePoolRedeemed;
+use crate::instructions::stake_escrow::create_stake_pda;
+use crate::stake_pool::{self, STAKE_POOL_PROGRAM_ID};
+
+#[derive(Accounts)]
+pub struct RedeemStakePoolStake<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    // Pays rent for the stake account that receives the split
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    /// CHECK: Parsed by the stake pool adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.approved_stake_pool @ SubscriptionError::InvalidStakePool
+    )]
+    pub stake_pool: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub validator_list: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    pub pool_withdraw_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Validator stake account in the pool to split from, validated by the stake pool program
+    #[account(mut)]
+    pub stake_to_split: UncheckedAccount<'info>,
+
+    /// CHECK: The subscription's stake PDA, created here to receive the split
+    #[account(
+        mut,
+        seeds = [b"stake", subscription.key().as_ref()],
+        bump
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub manager_fee_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool-tokens", subscription.key().as_ref()],
+        bump
+    )]
+    pub pool_token_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    /// CHECK: SPL stake pool program
+    #[account(address = STAKE_POOL_PROGRAM_ID)]
+    pub stake_pool_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Delayed redemption: pool tokens become an active stake account at the
+/// subscription's stake PDA, which is then unwound with deactivate_stake and
+/// withdraw_stake like natively staked escrow.
+pub fn handler(ctx: Context<RedeemStakePoolStake>, pool_tokens: u64) -> Result<()> {
+    require!(
+        pool_tokens > 0 && pool_tokens <= ctx.accounts.pool_token_vault.amount,
+        SubscriptionError::InvalidAmount
+    );
+    require!(
+        ctx.accounts.subscription.stake_account.is_none(),
+        SubscriptionError::StakeAccountExists
+    );
+
+    let subscription_key = ctx.accounts.subscription.key();
+    let subscription_seeds = &[
+        b"subscription",
+        ctx.accounts.subscription.user.as_ref(),
+        &[ctx.accounts.subscription.bump],
+    ];
+    let stake_seeds = &[
+        b"stake",
+        subscription_key.as_ref(),
+        &[ctx.bumps.stake_account],
+    ];
+
+    // The pool splits into an allocated, uninitialized stake account
+    let stake_space = stake::state::StakeStateV2::size_of();
+    let rent_reserve = ctx.accounts.rent.minimum_balance(stake_space);
+    create_stake_pda(
+        &ctx.accounts.user.to_account_info(),
+        &ctx.accounts.stake_account.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        rent_reserve,
+        &stake_seeds[..],
+    )?;
+
+    let pool = stake_pool::read_pool(&ctx.accounts.stake_pool.to_account_info())?;
+    let withdraw_ix = stake_pool::withdraw_stake(
+        &ctx.accounts.stake_pool.key(),
+        &pool,
+        &ctx.accounts.stake_to_split.key(),
+        &ctx.accounts.stake_account.key(),
+        &ctx.accounts.subscription_signer.key(),
+        &ctx.accounts.pool_token_vault.key(),
+        pool_tokens,
+    );
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &withdraw_ix,
+        &[
+            ctx.accounts.stake_pool.to_account_info(),
+            ctx.accounts.validator_list.to_account_info(),
+            ctx.accounts.pool_withdraw_authority.to_account_info(),
+            ctx.accounts.stake_to_split.to_account_info(),
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.pool_token_vault.to_account_info(),
+            ctx.accounts.manager_fee_account.to_account_info(),
+            ctx.accounts.pool_mint.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.stake_program.to_account_info(),
+            ctx.accounts.stake_pool_program.to_account_info(),
+        ],
+        &[&subscription_seeds[..]],
+    )?;
+
+    ctx.accounts.pool_token_vault.reload()?;
+    let pool = stake_pool::read_pool(&ctx.accounts.stake_pool.to_account_info())?;
+    let stake_lamports = ctx.accounts.stake_account.lamports();
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.pool_token_balance = ctx.accounts.pool_token_vault.amount;
+    subscription.pool_exchange_rate = pool.exchange_rate();
+    subscription.stake_account = Some(ctx.accounts.stake_account.key());
+    subscription.stake_delegated_amount = stake_lamports - rent_reserve;
+    subscription.staked_balance = Some(stake_lamports);
+    subscription.stake_activation_epoch = Some(ctx.accounts.clock.epoch);
+    subscription.stake_deactivation_epoch = None;
+
+    emit!(StakePoolRedeemed {
+        user: subscription.user,
+        pool_tokens,
+        lamports: stake_lamports,
+        delayed: true,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
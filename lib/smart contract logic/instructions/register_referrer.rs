@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+use crate::state::Referrer;
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(
+        init,
+        payer = wallet,
+        space = 8 + Referrer::LEN,
+        seeds = [b"referrer", wallet.key().as_ref()],
+        bump
+    )]
+    pub referrer: Account<'info, Referrer>,
+
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<RegisterReferrer>) -> Result<()> {
+    ctx.accounts.referrer.set_inner(Referrer {
+        wallet: ctx.accounts.wallet.key(),
+        referred_subscriptions: 0,
+        periods_paid: 0,
+        total_earned: 0,
+        bump: ctx.bumps.referrer,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use crate::state::{Settlement, HeldPayment};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct ReleaseHeldPayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"settlement", held_payment.recipient_token_account.as_ref()],
+        bump = settlement.bump
+    )]
+    pub settlement: Account<'info, Settlement>,
+
+    #[account(
+        mut,
+        close = payer,
+        seeds = [
+            b"held",
+            held_payment.recipient_token_account.as_ref(),
+            held_payment.subscription.as_ref(),
+            held_payment.due_at.to_le_bytes().as_ref()
+        ],
+        bump = held_payment.bump
+    )]
+    pub held_payment: Account<'info, HeldPayment>,
+
+    /// CHECK: Receives the held payment's rent
+    #[account(mut, address = held_payment.payer @ SubscriptionError::Unauthorized)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+/// Permissionless: once an undisputed payment's window has closed, add it to
+/// the merchant's claimable balance and refund the record's rent to the
+/// keeper that opened it
+pub fn handler(ctx: Context<ReleaseHeldPayment>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let held_payment = &ctx.accounts.held_payment;
+    require!(!held_payment.disputed, SubscriptionError::PaymentDisputed);
+    require!(now >= held_payment.release_at, SubscriptionError::PaymentStillHeld);
+
+    ctx.accounts.settlement.release(held_payment.amount, &held_payment.payouts)?;
+
+    emit!(HeldPaymentReleased {
+        recipient_token_account: held_payment.recipient_token_account,
+        user: held_payment.user,
+        due_at: held_payment.due_at,
+        amount: held_payment.amount,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct HeldPaymentReleased {
+    pub recipient_token_account: Pubkey,
+    pub user: Pubkey,
+    pub due_at: i64,
+    pub amount: u64,
+    pub timestamp: i64,
+}
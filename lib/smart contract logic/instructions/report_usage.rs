@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+use crate::state::{Subscription, Plan};
+use crate::error::SubscriptionError;
+use crate::billing::{self, FEE_AMOUNT_USDC};
+
+#[derive(Accounts)]
+pub struct ReportUsage<'info> {
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        has_one = merchant,
+        constraint = subscription.plan == Some(plan.key()) @ SubscriptionError::PlanMismatch
+    )]
+    pub plan: Account<'info, Plan>,
+
+    pub merchant: Signer<'info>,
+}
+
+/// Record usage on a metered plan. It is billed with the subscription's next
+/// charge; `record_id` is the merchant's own reference, echoed in the event
+/// so every billed unit can be traced back to a report.
+pub fn handler(ctx: Context<ReportUsage>, quantity: u64, record_id: u64) -> Result<()> {
+    let plan = &ctx.accounts.plan;
+    let subscription = &ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(plan.unit_price > 0, SubscriptionError::NotMetered);
+    require!(quantity > 0, SubscriptionError::InvalidAmount);
+
+    // Refuse usage the subscriber's cap wouldn't let the next charge collect
+    let period_units = subscription
+        .usage_units
+        .checked_add(quantity)
+        .ok_or(SubscriptionError::InvalidAmount)?;
+    let next_charge = period_units
+        .checked_mul(plan.unit_price)
+        .and_then(|usage| usage.checked_add(billing::period_price(subscription) + FEE_AMOUNT_USDC))
+        .ok_or(SubscriptionError::InvalidAmount)?;
+    require!(
+        billing::within_spending_cap(subscription, next_charge, next_charge),
+        SubscriptionError::SpendingCapExceeded
+    );
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.usage_units = period_units;
+
+    emit!(UsageRecorded {
+        user: subscription.user,
+        plan: plan.key(),
+        record_id,
+        quantity,
+        unit_price: plan.unit_price,
+        period_units,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct UsageRecorded {
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub record_id: u64,
+    pub quantity: u64,
+    pub unit_price: u64,
+    // Units reported since the last charge, including this record
+    pub period_units: u64,
+    pub timestamp: i64,
+}
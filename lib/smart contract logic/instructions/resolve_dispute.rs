@@ -0,0 +1,128 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::{PlatformConfig, Settlement, HeldPayment, Dispute, DisputeStatus};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = arbiter
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub arbiter: Signer<'info>,
+
+    #[account(mut)]
+    pub dispute: Account<'info, Dispute>,
+
+    #[account(
+        mut,
+        seeds = [b"settlement", dispute.recipient_token_account.as_ref()],
+        bump = settlement.bump
+    )]
+    pub settlement: Account<'info, Settlement>,
+
+    #[account(
+        mut,
+        close = held_payment_payer,
+        seeds = [
+            b"held",
+            dispute.recipient_token_account.as_ref(),
+            dispute.subscription.as_ref(),
+            dispute.due_at.to_le_bytes().as_ref()
+        ],
+        bump = held_payment.bump
+    )]
+    pub held_payment: Account<'info, HeldPayment>,
+
+    /// CHECK: Receives the held payment's rent
+    #[account(mut, address = held_payment.payer @ SubscriptionError::Unauthorized)]
+    pub held_payment_payer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"settlement-vault", dispute.recipient_token_account.as_ref()],
+        bump
+    )]
+    pub settlement_vault: Account<'info, TokenAccount>,
+
+    // Any of the subscriber's token accounts for the settlement's mint
+    #[account(
+        mut,
+        token::mint = settlement_vault.mint,
+        token::authority = dispute.user
+    )]
+    pub refund_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Refund `refund_amount` of the disputed payment to the subscriber and
+/// release the rest to the merchant: all of it refunds fully, zero sides
+/// with the merchant
+pub fn handler(ctx: Context<ResolveDispute>, refund_amount: u64) -> Result<()> {
+    let dispute = &ctx.accounts.dispute;
+    require!(dispute.status == DisputeStatus::Open, SubscriptionError::DisputeClosed);
+    require!(refund_amount <= dispute.amount, SubscriptionError::InvalidAmount);
+
+    let now = Clock::get()?.unix_timestamp;
+    let (user, amount) = (dispute.user, dispute.amount);
+
+    let released = amount - refund_amount;
+    let settlement = &mut ctx.accounts.settlement;
+    settlement.release(amount, &ctx.accounts.held_payment.payouts_of(released))?;
+
+    if refund_amount > 0 {
+        let recipient_token_account = settlement.recipient_token_account;
+        let seeds = &[
+            b"settlement",
+            recipient_token_account.as_ref(),
+            &[settlement.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.settlement_vault.to_account_info(),
+                    to: ctx.accounts.refund_token_account.to_account_info(),
+                    authority: ctx.accounts.settlement.to_account_info(),
+                },
+                signer,
+            ),
+            refund_amount,
+        )?;
+    }
+
+    let dispute = &mut ctx.accounts.dispute;
+    dispute.status = DisputeStatus::Resolved;
+    dispute.refunded = refund_amount;
+    dispute.resolved_at = Some(now);
+
+    emit!(DisputeResolved {
+        dispute: dispute.key(),
+        arbiter: ctx.accounts.arbiter.key(),
+        user,
+        recipient_token_account: dispute.recipient_token_account,
+        refunded: refund_amount,
+        released,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct DisputeResolved {
+    pub dispute: Pubkey,
+    pub arbiter: Pubkey,
+    pub user: Pubkey,
+    pub recipient_token_account: Pubkey,
+    pub refunded: u64,
+    // Added to the merchant's claimable settlement balance
+    pub released: u64,
+    pub timestamp: i64,
+}
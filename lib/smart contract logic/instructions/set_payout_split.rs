@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::state::{PayoutSplit, Payee};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct SetPayoutSplit<'info> {
+    #[account(
+        init_if_needed,
+        payer = merchant,
+        space = 8 + PayoutSplit::LEN,
+        seeds = [b"payout-split", recipient_token_account.key().as_ref()],
+        bump
+    )]
+    pub payout_split: Account<'info, PayoutSplit>,
+
+    #[account(
+        constraint = recipient_token_account.owner == merchant.key() @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Create or replace the split of a merchant payout account's period charges
+pub fn handler(ctx: Context<SetPayoutSplit>, payees: Vec<Payee>) -> Result<()> {
+    require!(PayoutSplit::is_valid(&payees), SubscriptionError::InvalidAmount);
+
+    ctx.accounts.payout_split.set_inner(PayoutSplit {
+        recipient_token_account: ctx.accounts.recipient_token_account.key(),
+        authority: ctx.accounts.merchant.key(),
+        payees: payees.clone(),
+        bump: ctx.bumps.payout_split,
+    });
+
+    emit!(PayoutSplitUpdated {
+        recipient_token_account: ctx.accounts.recipient_token_account.key(),
+        payees,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct PayoutSplitUpdated {
+    pub recipient_token_account: Pubkey,
+    pub payees: Vec<Payee>,
+    pub timestamp: i64,
+}
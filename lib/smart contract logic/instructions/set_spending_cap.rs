@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+use crate::state::Subscription;
+
+#[derive(Accounts)]
+pub struct SetSpendingCap<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    pub user: Signer<'info>,
+}
+
+/// Set the most that may be taken from the subscriber in one period, fees
+/// included (zero removes the limit), and optionally over the subscription's life
+pub fn handler(
+    ctx: Context<SetSpendingCap>,
+    max_amount_per_period: u64,
+    lifetime_cap: Option<u64>,
+) -> Result<()> {
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.max_amount_per_period = max_amount_per_period;
+    subscription.lifetime_cap = lifetime_cap;
+
+    emit!(SpendingCapSet {
+        user: subscription.user,
+        max_amount_per_period,
+        lifetime_cap,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct SpendingCapSet {
+    pub user: Pubkey,
+    pub max_amount_per_period: u64,
+    pub lifetime_cap: Option<u64>,
+    pub timestamp: i64,
+}
@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use crate::state::{Subscription, PlatformConfig, PendingRefund};
+use crate::error::SubscriptionError;
+use crate::instructions::settle_refund_stake::RefundSettled;
+use crate::instructions::withdraw_lent_escrow::LentEscrowWithdrawn;
+use crate::lending;
+
+#[derive(Accounts)]
+pub struct SettleRefundLending<'info> {
+    #[account(
+        mut,
+        seeds = [b"pending-refund", subscription.key().as_ref()],
+        bump = pending_refund.bump,
+        has_one = subscription
+    )]
+    pub pending_refund: Account<'info, PendingRefund>,
+
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    /// CHECK: Receives the pending refund's rent once everything is settled
+    #[account(mut, address = subscription.user @ SubscriptionError::Unauthorized)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = pending_refund.destination_token_account @ SubscriptionError::Unauthorized
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: Parsed by the lending adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.lending_reserve @ SubscriptionError::InvalidLendingReserve
+    )]
+    pub lending_reserve: UncheckedAccount<'info>,
+
+    /// CHECK: The reserve's liquidity price oracle, checked against the reserve by the lending adapter
+    pub reserve_liquidity_oracle: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the lending program
+    #[account(mut)]
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub reserve_collateral_mint: Account<'info, Mint>,
+
+    /// CHECK: Validated by the lending program
+    pub lending_market: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the lending program
+    pub lending_market_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", subscription.key().as_ref()],
+        bump
+    )]
+    pub collateral_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Lending program, address pinned by the platform config
+    #[account(address = platform_config.lending_program @ SubscriptionError::InvalidLendingReserve)]
+    pub lending_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Permissionless: redeem all of a canceled subscription's collateral and
+/// refund it. Yield realized on the way out goes wholly to the subscriber.
+pub fn handler(ctx: Context<SettleRefundLending>) -> Result<()> {
+    require!(
+        !ctx.accounts.subscription.is_active,
+        SubscriptionError::InvalidSubscriptionState
+    );
+
+    let collateral = ctx.accounts.subscription.lent_collateral;
+    require!(collateral > 0, SubscriptionError::InvalidSubscriptionState);
+
+    let reserve = lending::refresh_and_read_reserve(
+        &ctx.accounts.lending_reserve.to_account_info(),
+        &ctx.accounts.reserve_liquidity_oracle.to_account_info(),
+        &ctx.accounts.clock.to_account_info(),
+        &ctx.accounts.lending_program.to_account_info(),
+    )?;
+
+    let redeem_ix = lending::redeem_collateral(
+        &ctx.accounts.lending_program.key(),
+        &ctx.accounts.lending_reserve.key(),
+        &reserve,
+        &ctx.accounts.collateral_vault.key(),
+        &ctx.accounts.escrow_token_account.key(),
+        &ctx.accounts.subscription_signer.key(),
+        collateral,
+    );
+
+    let escrow_before = ctx.accounts.escrow_token_account.amount;
+    let signer_seeds: &[&[u8]] = &[
+        b"subscription",
+        ctx.accounts.subscription.user.as_ref(),
+        &[ctx.accounts.subscription.bump],
+    ];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &redeem_ix,
+        &[
+            ctx.accounts.collateral_vault.to_account_info(),
+            ctx.accounts.escrow_token_account.to_account_info(),
+            ctx.accounts.lending_reserve.to_account_info(),
+            ctx.accounts.reserve_collateral_mint.to_account_info(),
+            ctx.accounts.reserve_liquidity_supply.to_account_info(),
+            ctx.accounts.lending_market.to_account_info(),
+            ctx.accounts.lending_market_authority.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.lending_program.to_account_info(),
+        ],
+        &[signer_seeds],
+    )?;
+
+    ctx.accounts.escrow_token_account.reload()?;
+    let received = ctx.accounts.escrow_token_account.amount - escrow_before;
+    let (principal, interest) = lending::split_redemption(
+        ctx.accounts.subscription.lent_principal,
+        collateral,
+        collateral,
+        received,
+    );
+
+    // Everything in escrow now belongs to the subscriber, including any
+    // dust that arrived after cancel
+    let refund = ctx.accounts.escrow_token_account.amount;
+    token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.escrow_token_account.to_account_info(),
+                to: ctx.accounts.destination_token_account.to_account_info(),
+                authority: ctx.accounts.subscription_signer.clone(),
+            },
+            &[signer_seeds],
+        ),
+        refund,
+    )?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.lent_principal = 0;
+    subscription.lent_collateral = 0;
+    subscription.accrued_interest = 0;
+    subscription.yield_credited += interest;
+
+    emit!(LentEscrowWithdrawn {
+        user: subscription.user,
+        collateral,
+        principal,
+        interest,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    if !subscription.has_illiquid_positions() {
+        ctx.accounts.pending_refund.close(ctx.accounts.user.to_account_info())?;
+        emit!(RefundSettled {
+            user: subscription.user,
+            timestamp: ctx.accounts.clock.unix_timestamp,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake;
+use anchor_lang::solana_program::stake::instruction as stake_instruction;
+use anchor_lang::solana_program::sysvar::stake_history;
+use crate::state::{Subscription, PendingRefund};
+use crate::error::SubscriptionError;
+use crate::instructions::withdraw_stake::StakeWithdrawn;
+
+#[derive(Accounts)]
+pub struct SettleRefundStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"pending-refund", subscription.key().as_ref()],
+        bump = pending_refund.bump,
+        has_one = subscription
+    )]
+    pub pending_refund: Account<'info, PendingRefund>,
+
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    /// CHECK: Receives the withdrawn SOL and the pending refund's rent
+    #[account(mut, address = subscription.user @ SubscriptionError::Unauthorized)]
+    pub user: UncheckedAccount<'info>,
+
+    /// CHECK: Must be the subscription's stake PDA recorded by stake_escrow
+    #[account(
+        mut,
+        seeds = [b"stake", subscription.key().as_ref()],
+        bump,
+        constraint = subscription.stake_account == Some(stake_account.key()) @ SubscriptionError::InvalidStakeAccount
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Stake history sysvar required by withdraw
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Permissionless: unwind a canceled subscription's stake in two calls. The
+/// first starts the cooldown if the user hadn't already; once the cooldown
+/// epoch has passed the second withdraws everything to the user.
+pub fn handler(ctx: Context<SettleRefundStake>) -> Result<()> {
+    require!(
+        !ctx.accounts.subscription.is_active,
+        SubscriptionError::InvalidSubscriptionState
+    );
+
+    let signer_seeds: &[&[u8]] = &[
+        b"subscription",
+        ctx.accounts.subscription.user.as_ref(),
+        &[ctx.accounts.subscription.bump],
+    ];
+
+    let deactivation_epoch = match ctx.accounts.subscription.stake_deactivation_epoch {
+        Some(epoch) => epoch,
+        None => {
+            let deactivate_ix = stake_instruction::deactivate_stake(
+                &ctx.accounts.stake_account.key(),
+                &ctx.accounts.subscription_signer.key(),
+            );
+
+            anchor_lang::solana_program::program::invoke_signed(
+                &deactivate_ix,
+                &[
+                    ctx.accounts.stake_account.to_account_info(),
+                    ctx.accounts.clock.to_account_info(),
+                    ctx.accounts.subscription_signer.clone(),
+                    ctx.accounts.stake_program.to_account_info(),
+                ],
+                &[signer_seeds],
+            )?;
+
+            let subscription = &mut ctx.accounts.subscription;
+            subscription.staked_balance = Some(ctx.accounts.stake_account.lamports());
+            subscription.stake_deactivation_epoch = Some(ctx.accounts.clock.epoch);
+            return Ok(());
+        }
+    };
+
+    require!(
+        ctx.accounts.clock.epoch > deactivation_epoch,
+        SubscriptionError::StakeCooldownPending
+    );
+
+    let withdrawn = ctx.accounts.stake_account.lamports();
+
+    let withdraw_ix = stake_instruction::withdraw(
+        &ctx.accounts.stake_account.key(),
+        &ctx.accounts.subscription_signer.key(),
+        &ctx.accounts.user.key(),
+        withdrawn,
+        None,
+    );
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &withdraw_ix,
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.stake_history.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.stake_program.to_account_info(),
+        ],
+        &[signer_seeds],
+    )?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    let stake_account = subscription.stake_account.take().unwrap_or_default();
+    subscription.staked_balance = None;
+    subscription.stake_delegated_amount = 0;
+    subscription.stake_activation_epoch = None;
+    subscription.stake_deactivation_epoch = None;
+
+    emit!(StakeWithdrawn {
+        user: subscription.user,
+        stake_account,
+        amount: withdrawn,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    if !subscription.has_illiquid_positions() {
+        ctx.accounts.pending_refund.close(ctx.accounts.user.to_account_info())?;
+        emit!(RefundSettled {
+            user: subscription.user,
+            timestamp: ctx.accounts.clock.unix_timestamp,
+        });
+    }
+
+    Ok(())
+}
+
+#[event]
+pub struct RefundSettled {
+    pub user: Pubkey,
+    pub timestamp: i64,
+}
@@ -0,0 +1,138 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{Subscription, PlatformConfig, PendingRefund};
+use crate::error::SubscriptionError;
+use crate::stake_pool::STAKE_POOL_PROGRAM_ID;
+use crate::instructions::redeem_stake_pool_sol::{withdraw_pool_sol, PoolSolAccounts, StakePoolRedeemed};
+use crate::instructions::settle_refund_stake::RefundSettled;
+
+#[derive(Accounts)]
+pub struct SettleRefundStakePool<'info> {
+    #[account(
+        mut,
+        seeds = [b"pending-refund", subscription.key().as_ref()],
+        bump = pending_refund.bump,
+        has_one = subscription
+    )]
+    pub pending_refund: Account<'info, PendingRefund>,
+
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    /// CHECK: Receives the redeemed SOL and the pending refund's rent
+    #[account(mut, address = subscription.user @ SubscriptionError::Unauthorized)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    /// CHECK: Parsed by the stake pool adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.approved_stake_pool @ SubscriptionError::InvalidStakePool
+    )]
+    pub stake_pool: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    pub pool_withdraw_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub reserve_stake: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the stake pool program
+    #[account(mut)]
+    pub manager_fee_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub pool_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool-tokens", subscription.key().as_ref()],
+        bump
+    )]
+    pub pool_token_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Stake history sysvar required by the reserve withdrawal
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = anchor_lang::solana_program::stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    /// CHECK: SPL stake pool program
+    #[account(address = STAKE_POOL_PROGRAM_ID)]
+    pub stake_pool_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Permissionless: redeem all of a canceled subscription's pool tokens out of
+/// the pool's SOL reserve and send the SOL to the user. Fails while the
+/// reserve can't cover them; the user can still take the delayed route with
+/// redeem_stake_pool_stake and settle the stake account afterwards.
+pub fn handler(ctx: Context<SettleRefundStakePool>) -> Result<()> {
+    require!(
+        !ctx.accounts.subscription.is_active,
+        SubscriptionError::InvalidSubscriptionState
+    );
+
+    // Already redeemed by the user directly: only the record is left to close
+    let pool_tokens = ctx.accounts.pool_token_vault.amount;
+    if pool_tokens > 0 {
+        let lamports = withdraw_pool_sol(
+            &mut ctx.accounts.subscription,
+            &mut ctx.accounts.pool_token_vault,
+            &PoolSolAccounts {
+                stake_pool: ctx.accounts.stake_pool.to_account_info(),
+                pool_withdraw_authority: ctx.accounts.pool_withdraw_authority.to_account_info(),
+                subscription_signer: ctx.accounts.subscription_signer.clone(),
+                reserve_stake: ctx.accounts.reserve_stake.to_account_info(),
+                lamports_to: ctx.accounts.user.to_account_info(),
+                manager_fee_account: ctx.accounts.manager_fee_account.to_account_info(),
+                pool_mint: ctx.accounts.pool_mint.to_account_info(),
+                clock: ctx.accounts.clock.to_account_info(),
+                stake_history: ctx.accounts.stake_history.to_account_info(),
+                stake_program: ctx.accounts.stake_program.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+                stake_pool_program: ctx.accounts.stake_pool_program.to_account_info(),
+            },
+            pool_tokens,
+        )?;
+
+        emit!(StakePoolRedeemed {
+            user: ctx.accounts.subscription.user,
+            pool_tokens,
+            lamports,
+            delayed: false,
+            timestamp: ctx.accounts.clock.unix_timestamp,
+        });
+    } else {
+        ctx.accounts.subscription.pool_token_balance = 0;
+    }
+
+    let subscription = &ctx.accounts.subscription;
+    if !subscription.has_illiquid_positions() {
+        ctx.accounts.pending_refund.close(ctx.accounts.user.to_account_info())?;
+        emit!(RefundSettled {
+            user: subscription.user,
+            timestamp: ctx.accounts.clock.unix_timestamp,
+        });
+    }
+
+    Ok(())
+}
@@ -3,12 +3,12 @@ use anchor_lang::solana_program::stake;
 use anchor_lang::solana_program::stake::instruction as stake_instruction;
 use anchor_lang::solana_program::stake::state::{Authorized, Lockup};
 use anchor_lang::solana_program::system_instruction;
-use crate::state::Subscription;
+use crate::state::{Subscription, PlatformConfig};
 use crate::error::SubscriptionError;
 
 #[derive(Accounts)]
 pub struct StakeEscrow<'info> {
-    #[account(mut)]
+    #[account(mut, has_one = user)]
     pub subscription: Account<'info, Subscription>,
 
     #[account(
@@ -19,14 +19,42 @@ pub struct StakeEscrow<'info> {
     /// CHECK: This is the PDA signer of the subscription
     pub subscription_signer: AccountInfo<'info>,
 
-    /// CHECK: New stake account owned by the subscription
+    // Funds the stake account; the SOL stays escrowed under the subscription's authority
     #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Created here as a PDA of the subscription; staker and withdrawer authority go to the subscription PDA
+    #[account(
+        mut,
+        seeds = [b"stake", subscription.key().as_ref()],
+        bump
+    )]
     pub stake_account: UncheckedAccount<'info>,
 
-    /// CHECK: BlazeStake validator's vote account
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    /// CHECK: BlazeStake validator's vote account, restricted to the admin-approved list
+    #[account(
+        constraint = platform_config.approved_validators.contains(&validator_vote.key()) @ SubscriptionError::ValidatorNotApproved
+    )]
     pub validator_vote: UncheckedAccount<'info>,
 
-    #[account(mut)]
+    /// CHECK: Stake config sysvar required by delegate_stake
+    #[account(address = stake::config::ID)]
+    pub stake_config: UncheckedAccount<'info>,
+
+    /// CHECK: Stake history sysvar required by delegate_stake
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
 
     pub rent: Sysvar<'info, Rent>,
@@ -37,6 +65,15 @@ pub fn handler(
     ctx: Context<StakeEscrow>,
     stake_lamports: u64,
 ) -> Result<()> {
+    require!(
+        ctx.accounts.subscription.stake_account.is_none(),
+        SubscriptionError::StakeAccountExists
+    );
+
+    let stake_space = stake::state::StakeStateV2::size_of();
+    let rent_reserve = ctx.accounts.rent.minimum_balance(stake_space);
+    require!(stake_lamports > rent_reserve, SubscriptionError::InvalidAmount);
+
     // Derive signer seeds
     let seeds = &[
         b"subscription",
@@ -45,23 +82,20 @@ pub fn handler(
     ];
     let signer_seeds = &[&seeds[..]];
 
+    let subscription_key = ctx.accounts.subscription.key();
+    let stake_seeds = &[
+        b"stake",
+        subscription_key.as_ref(),
+        &[ctx.bumps.stake_account],
+    ];
+
     // Create the stake account
-    let create_stake_ix = system_instruction::create_account(
-        &ctx.accounts.subscription_signer.key(),
-        &ctx.accounts.stake_account.key(),
+    create_stake_pda(
+        &ctx.accounts.user.to_account_info(),
+        &ctx.accounts.stake_account.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
         stake_lamports,
-        stake::state::StakeState::size_of() as u64,
-        &stake::program::ID,
-    );
-
-    anchor_lang::solana_program::program::invoke_signed(
-        &create_stake_ix,
-        &[
-            ctx.accounts.subscription_signer.clone(),
-            ctx.accounts.stake_account.to_account_info(),
-            ctx.accounts.system_program.to_account_info(),
-        ],
-        signer_seeds,
+        &stake_seeds[..],
     )?;
 
     // Initialize stake account
@@ -77,13 +111,13 @@ pub fn handler(
         &lockup,
     );
 
-    anchor_lang::solana_program::program::invoke_signed(
+    anchor_lang::solana_program::program::invoke(
         &init_stake_ix,
         &[
             ctx.accounts.stake_account.to_account_info(),
             ctx.accounts.rent.to_account_info(),
+            ctx.accounts.stake_program.to_account_info(),
         ],
-        signer_seeds,
     )?;
 
     // Delegate to BlazeStake validator
@@ -99,9 +133,94 @@ pub fn handler(
             ctx.accounts.stake_account.to_account_info(),
             ctx.accounts.validator_vote.to_account_info(),
             ctx.accounts.clock.to_account_info(),
+            ctx.accounts.stake_history.to_account_info(),
+            ctx.accounts.stake_config.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.stake_program.to_account_info(),
         ],
         signer_seeds,
     )?;
 
+    // Lamports already sitting at the address were staked along with the deposit
+    let stake_lamports = ctx.accounts.stake_account.lamports();
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.stake_account = Some(ctx.accounts.stake_account.key());
+    subscription.stake_delegated_amount = stake_lamports - rent_reserve;
+    subscription.staked_balance = Some(stake_lamports);
+    subscription.stake_activation_epoch = Some(ctx.accounts.clock.epoch);
+    subscription.stake_deactivation_epoch = None;
+
+    Ok(())
+}
+
+/// Create the subscription's stake PDA with at least `lamports`, owned by the
+/// stake program
+pub(crate) fn create_stake_pda<'info>(
+    payer: &AccountInfo<'info>,
+    stake_account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    lamports: u64,
+    stake_seeds: &[&[u8]],
+) -> Result<()> {
+    create_pda(
+        payer,
+        stake_account,
+        system_program,
+        lamports,
+        stake::state::StakeStateV2::size_of(),
+        &stake::program::ID,
+        stake_seeds,
+    )
+}
+
+/// Create the PDA at `seeds` with `space` bytes and at least `lamports`,
+/// owned by `owner`. create_account fails on an address that already holds
+/// lamports, and anyone can send SOL to a PDA, so a pre-funded address is
+/// topped up and then allocated and assigned instead.
+pub(crate) fn create_pda<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    lamports: u64,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let current_lamports = account.lamports();
+
+    if current_lamports == 0 {
+        anchor_lang::solana_program::program::invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                lamports,
+                space as u64,
+                owner,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        return Ok(());
+    }
+
+    let top_up = lamports.saturating_sub(current_lamports);
+    if top_up > 0 {
+        anchor_lang::solana_program::program::invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    anchor_lang::solana_program::program::invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    anchor_lang::solana_program::program::invoke_signed(
+        &system_instruction::assign(account.key, owner),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+
     Ok(())
 }
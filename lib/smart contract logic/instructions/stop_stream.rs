@@ -0,0 +1,103 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::Subscription;
+use crate::error::SubscriptionError;
+use crate::instructions::withdraw_stream::pay_out_stream;
+
+#[derive(Accounts)]
+pub struct StopStream<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", user.key().as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    // Receives whatever the stream accrued but the merchant hadn't withdrawn
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// End a stream: the merchant is paid up to now and the unstreamed rest of
+/// the escrow goes back to the user
+pub fn handler(ctx: Context<StopStream>) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(subscription.stream_rate > 0, SubscriptionError::NotStreaming);
+    require!(!subscription.has_illiquid_positions(), SubscriptionError::EscrowNotLiquid);
+
+    let now = Clock::get()?.unix_timestamp;
+    let merchant_amount = pay_out_stream(
+        &mut ctx.accounts.subscription,
+        &ctx.accounts.escrow_token_account,
+        &ctx.accounts.recipient_token_account,
+        &ctx.accounts.subscription_signer,
+        &ctx.accounts.token_program,
+        now,
+    )?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    let refunded_amount = ctx.accounts.escrow_token_account.amount - merchant_amount;
+
+    if refunded_amount > 0 {
+        let seeds = &[
+            b"subscription",
+            subscription.user.as_ref(),
+            &[subscription.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_token_account.to_account_info(),
+                    to: ctx.accounts.destination_token_account.to_account_info(),
+                    authority: ctx.accounts.subscription_signer.clone(),
+                },
+                signer,
+            ),
+            refunded_amount,
+        )?;
+    }
+
+    subscription.is_active = false;
+
+    emit!(StreamStopped {
+        user: subscription.user,
+        merchant_amount,
+        refunded_amount,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct StreamStopped {
+    pub user: Pubkey,
+    // Final payout to the merchant, also reported by StreamWithdrawn
+    pub merchant_amount: u64,
+    pub refunded_amount: u64,
+    pub timestamp: i64,
+}
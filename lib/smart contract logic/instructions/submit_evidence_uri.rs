@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::state::{Dispute, DisputeStatus};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct SubmitEvidenceUri<'info> {
+    #[account(mut)]
+    pub dispute: Account<'info, Dispute>,
+
+    // Identifies the merchant side of the dispute
+    #[account(address = dispute.recipient_token_account @ SubscriptionError::Unauthorized)]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    // The disputing subscriber or the merchant
+    pub submitter: Signer<'info>,
+}
+
+/// Record a link to either side's evidence, replacing what that side submitted before
+pub fn handler(ctx: Context<SubmitEvidenceUri>, uri: String) -> Result<()> {
+    require!(uri.len() <= Dispute::MAX_URI_LEN, SubscriptionError::EvidenceUriTooLong);
+
+    let submitter = ctx.accounts.submitter.key();
+    let merchant = ctx.accounts.recipient_token_account.owner;
+    let dispute = &mut ctx.accounts.dispute;
+    require!(dispute.status == DisputeStatus::Open, SubscriptionError::DisputeClosed);
+
+    if submitter == dispute.user {
+        dispute.user_evidence_uri = uri.clone();
+    } else if submitter == merchant {
+        dispute.merchant_evidence_uri = uri.clone();
+    } else {
+        return Err(SubscriptionError::Unauthorized.into());
+    }
+
+    emit!(EvidenceSubmitted {
+        dispute: dispute.key(),
+        submitter,
+        uri,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct EvidenceSubmitted {
+    pub dispute: Pubkey,
+    pub submitter: Pubkey,
+    pub uri: String,
+    pub timestamp: i64,
+}
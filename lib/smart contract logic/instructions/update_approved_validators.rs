@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct UpdateApprovedValidators<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+// Replaces the whole list; existing stake stays delegated until its owner deactivates it
+pub fn handler(
+    ctx: Context<UpdateApprovedValidators>,
+    approved_validators: Vec<Pubkey>,
+) -> Result<()> {
+    require!(
+        approved_validators.len() <= PlatformConfig::MAX_VALIDATORS,
+        SubscriptionError::InvalidAmount
+    );
+
+    ctx.accounts.platform_config.approved_validators = approved_validators;
+    Ok(())
+}
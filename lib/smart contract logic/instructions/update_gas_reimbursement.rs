@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+
+#[derive(Accounts)]
+pub struct UpdateGasReimbursement<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateGasReimbursement>,
+    gas_reimbursement_lamports: u64,
+    price_oracle: Pubkey,
+) -> Result<()> {
+    let platform_config = &mut ctx.accounts.platform_config;
+
+    platform_config.gas_reimbursement_lamports = gas_reimbursement_lamports;
+    platform_config.price_oracle = price_oracle;
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+use crate::state::constants::BPS_DENOMINATOR;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct UpdateKeeperReward<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateKeeperReward>,
+    keeper_reward_bps: u16,
+) -> Result<()> {
+    require!(
+        keeper_reward_bps as u64 <= BPS_DENOMINATOR,
+        SubscriptionError::InvalidAmount
+    );
+
+    ctx.accounts.platform_config.keeper_reward_bps = keeper_reward_bps;
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+
+#[derive(Accounts)]
+pub struct UpdateLendingReserve<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateLendingReserve>,
+    lending_program: Pubkey,
+    lending_reserve: Pubkey,
+) -> Result<()> {
+    let platform_config = &mut ctx.accounts.platform_config;
+    platform_config.lending_program = lending_program;
+    platform_config.lending_reserve = lending_reserve;
+    Ok(())
+}
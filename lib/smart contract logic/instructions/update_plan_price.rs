@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use crate::state::Plan;
+use crate::state::constants::PRICE_CHANGE_NOTICE_SECONDS;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct UpdatePlanPrice<'info> {
+    #[account(
+        mut,
+        seeds = [b"plan", merchant.key().as_ref(), plan.plan_id.to_le_bytes().as_ref()],
+        bump = plan.bump,
+        has_one = merchant
+    )]
+    pub plan: Account<'info, Plan>,
+
+    pub merchant: Signer<'info>,
+}
+
+/// Decreases apply to every subscriber at their next charge. Increases are
+/// announced and take effect after the notice period, and only for
+/// subscribers who accept them; the rest end with their paid period.
+pub fn handler(ctx: Context<UpdatePlanPrice>, new_price: u64) -> Result<()> {
+    require!(new_price > 0, SubscriptionError::InvalidAmount);
+
+    let now = Clock::get()?.unix_timestamp;
+    let plan = &mut ctx.accounts.plan;
+    plan.roll_forward(now);
+
+    let old_price = plan.price;
+    let effective_at = if new_price <= old_price {
+        plan.price = new_price;
+        plan.pending_price = 0;
+        plan.price_effective_at = 0;
+        now
+    } else {
+        plan.pending_price = new_price;
+        plan.price_effective_at = now + PRICE_CHANGE_NOTICE_SECONDS;
+        plan.price_effective_at
+    };
+
+    emit!(PlanPriceChanged {
+        plan: plan.key(),
+        old_price,
+        new_price,
+        effective_at,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct PlanPriceChanged {
+    pub plan: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+    pub effective_at: i64,
+    pub timestamp: i64,
+}
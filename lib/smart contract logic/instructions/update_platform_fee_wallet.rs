@@ -1,6 +1,5 @@
 use anchor_lang::prelude::*;
 use crate::state::PlatformConfig;
-use crate::error::SubscriptionError;
 
 #[derive(Accounts)]
 pub struct UpdatePlatformFeeWallet<'info> {
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+use crate::state::constants::BPS_DENOMINATOR;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct UpdateReferralProgram<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateReferralProgram>,
+    referral_share_bps: u16,
+    referral_periods: u32,
+) -> Result<()> {
+    require!(
+        referral_share_bps as u64 <= BPS_DENOMINATOR,
+        SubscriptionError::InvalidAmount
+    );
+
+    let platform_config = &mut ctx.accounts.platform_config;
+    platform_config.referral_share_bps = referral_share_bps;
+    platform_config.referral_periods = referral_periods;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct UpdateSettlementDelay<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+/// Payments already held keep the release time they were given
+pub fn handler(ctx: Context<UpdateSettlementDelay>, settlement_delay: i64) -> Result<()> {
+    require!(settlement_delay >= 0, SubscriptionError::InvalidAmount);
+
+    ctx.accounts.platform_config.settlement_delay = settlement_delay;
+    Ok(())
+}
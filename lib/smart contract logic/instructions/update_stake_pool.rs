@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+use crate::state::PlatformConfig;
+
+#[derive(Accounts)]
+pub struct UpdateStakePool<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateStakePool>,
+    approved_stake_pool: Pubkey,
+) -> Result<()> {
+    ctx.accounts.platform_config.approved_stake_pool = approved_stake_pool;
+    Ok(())
+}
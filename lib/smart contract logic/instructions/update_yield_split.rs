@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+use crate::state::{PlatformConfig, YieldSplit};
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct UpdateYieldSplit<'info> {
+    #[account(
+        mut,
+        seeds = [b"platform-config"],
+        bump = platform_config.bump,
+        has_one = admin
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateYieldSplit>,
+    yield_split: YieldSplit,
+) -> Result<()> {
+    require!(yield_split.is_valid(), SubscriptionError::InvalidAmount);
+
+    ctx.accounts.platform_config.yield_split = yield_split;
+    Ok(())
+}
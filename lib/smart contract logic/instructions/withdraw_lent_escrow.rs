@@ -0,0 +1,174 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+use crate::state::{Subscription, PlatformConfig};
+use crate::error::SubscriptionError;
+use crate::{billing, lending, oracle};
+
+#[derive(Accounts)]
+pub struct WithdrawLentEscrow<'info> {
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"platform-config"],
+        bump = platform_config.bump
+    )]
+    pub platform_config: Account<'info, PlatformConfig>,
+
+    /// CHECK: Read through the oracle adapter, address pinned by the platform config
+    #[account(address = platform_config.price_oracle)]
+    pub price_oracle: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: Parsed by the lending adapter, address pinned by the platform config
+    #[account(
+        mut,
+        address = platform_config.lending_reserve @ SubscriptionError::InvalidLendingReserve
+    )]
+    pub lending_reserve: UncheckedAccount<'info>,
+
+    /// CHECK: The reserve's liquidity price oracle, checked against the reserve by the lending adapter
+    pub reserve_liquidity_oracle: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the lending program
+    #[account(mut)]
+    pub reserve_liquidity_supply: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub reserve_collateral_mint: Account<'info, Mint>,
+
+    /// CHECK: Validated by the lending program
+    pub lending_market: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the lending program
+    pub lending_market_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", subscription.key().as_ref()],
+        bump
+    )]
+    pub collateral_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Lending program, address pinned by the platform config
+    #[account(address = platform_config.lending_program @ SubscriptionError::InvalidLendingReserve)]
+    pub lending_program: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Permissionless: once a payment is due, redeem just enough collateral to
+/// cover the charge. Keepers put this in front of process_payment; when the
+/// escrow already covers the charge it does nothing.
+pub fn handler(ctx: Context<WithdrawLentEscrow>) -> Result<()> {
+    let now = ctx.accounts.clock.unix_timestamp;
+    billing::check_due(&ctx.accounts.subscription, now)?;
+
+    let sol_price = oracle::read_sol_price(&ctx.accounts.price_oracle.to_account_info())?;
+    let gas_reimbursement = oracle::lamports_to_usdc(
+        ctx.accounts.platform_config.gas_reimbursement_lamports,
+        sol_price,
+    )?;
+    let charge = billing::compute_charge(
+        &ctx.accounts.subscription,
+        &ctx.accounts.platform_config,
+        gas_reimbursement,
+        now,
+    )?;
+
+    let escrow_before = ctx.accounts.escrow_token_account.amount;
+    let shortfall = charge.total()?.saturating_sub(escrow_before);
+    if shortfall == 0 {
+        return Ok(());
+    }
+
+    let reserve = lending::refresh_and_read_reserve(
+        &ctx.accounts.lending_reserve.to_account_info(),
+        &ctx.accounts.reserve_liquidity_oracle.to_account_info(),
+        &ctx.accounts.clock.to_account_info(),
+        &ctx.accounts.lending_program.to_account_info(),
+    )?;
+    let collateral = reserve
+        .collateral_for(shortfall)
+        .min(ctx.accounts.subscription.lent_collateral);
+    require!(collateral > 0, SubscriptionError::InsufficientFunds);
+
+    let redeem_ix = lending::redeem_collateral(
+        &ctx.accounts.lending_program.key(),
+        &ctx.accounts.lending_reserve.key(),
+        &reserve,
+        &ctx.accounts.collateral_vault.key(),
+        &ctx.accounts.escrow_token_account.key(),
+        &ctx.accounts.subscription_signer.key(),
+        collateral,
+    );
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &redeem_ix,
+        &[
+            ctx.accounts.collateral_vault.to_account_info(),
+            ctx.accounts.escrow_token_account.to_account_info(),
+            ctx.accounts.lending_reserve.to_account_info(),
+            ctx.accounts.reserve_collateral_mint.to_account_info(),
+            ctx.accounts.reserve_liquidity_supply.to_account_info(),
+            ctx.accounts.lending_market.to_account_info(),
+            ctx.accounts.lending_market_authority.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.lending_program.to_account_info(),
+        ],
+        &[&[
+            b"subscription",
+            ctx.accounts.subscription.user.as_ref(),
+            &[ctx.accounts.subscription.bump],
+        ][..]],
+    )?;
+
+    ctx.accounts.escrow_token_account.reload()?;
+    let received = ctx.accounts.escrow_token_account.amount - escrow_before;
+
+    let subscription = &mut ctx.accounts.subscription;
+    let (principal, interest) = lending::split_redemption(
+        subscription.lent_principal,
+        subscription.lent_collateral,
+        collateral,
+        received,
+    );
+    subscription.lent_principal -= principal;
+    subscription.lent_collateral -= collateral;
+    subscription.accrued_interest += interest;
+
+    emit!(LentEscrowWithdrawn {
+        user: subscription.user,
+        collateral,
+        principal,
+        interest,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct LentEscrowWithdrawn {
+    pub user: Pubkey,
+    pub collateral: u64,
+    pub principal: u64,
+    pub interest: u64,
+    pub timestamp: i64,
+}
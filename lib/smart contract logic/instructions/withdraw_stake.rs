@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake;
+use anchor_lang::solana_program::stake::instruction as stake_instruction;
+use anchor_lang::solana_program::sysvar::stake_history;
+use crate::state::Subscription;
+use crate::error::SubscriptionError;
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+    #[account(mut, has_one = user)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    // Receives the withdrawn SOL
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Must be the subscription's stake PDA recorded by stake_escrow
+    #[account(
+        mut,
+        seeds = [b"stake", subscription.key().as_ref()],
+        bump,
+        constraint = subscription.stake_account == Some(stake_account.key()) @ SubscriptionError::InvalidStakeAccount
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Stake history sysvar required by withdraw
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Native stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Phase three of the staking lifecycle: once the cooldown epoch has passed,
+/// drain the stake account (principal plus rewards) back to the user.
+pub fn handler(ctx: Context<WithdrawStake>) -> Result<()> {
+    let deactivation_epoch = ctx
+        .accounts
+        .subscription
+        .stake_deactivation_epoch
+        .ok_or(SubscriptionError::StakeNotDeactivated)?;
+
+    require!(
+        ctx.accounts.clock.epoch > deactivation_epoch,
+        SubscriptionError::StakeCooldownPending
+    );
+
+    let withdrawn = ctx.accounts.stake_account.lamports();
+
+    let withdraw_ix = stake_instruction::withdraw(
+        &ctx.accounts.stake_account.key(),
+        &ctx.accounts.subscription_signer.key(),
+        &ctx.accounts.user.key(),
+        withdrawn,
+        None,
+    );
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &withdraw_ix,
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.stake_history.to_account_info(),
+            ctx.accounts.subscription_signer.clone(),
+            ctx.accounts.stake_program.to_account_info(),
+        ],
+        &[&[
+            b"subscription",
+            ctx.accounts.subscription.user.as_ref(),
+            &[ctx.accounts.subscription.bump],
+        ][..]],
+    )?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    let stake_account = subscription.stake_account.take().unwrap_or_default();
+    subscription.staked_balance = None;
+    subscription.stake_delegated_amount = 0;
+    subscription.stake_activation_epoch = None;
+    subscription.stake_deactivation_epoch = None;
+
+    emit!(StakeWithdrawn {
+        user: subscription.user,
+        stake_account,
+        amount: withdrawn,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[event]
+pub struct StakeWithdrawn {
+    pub user: Pubkey,
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
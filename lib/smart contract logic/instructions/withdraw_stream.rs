@@ -0,0 +1,113 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::state::Subscription;
+use crate::error::SubscriptionError;
+use crate::billing;
+
+#[derive(Accounts)]
+pub struct WithdrawStream<'info> {
+    #[account(mut)]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.user.as_ref()],
+        bump = subscription.bump
+    )]
+    /// CHECK: This is the PDA signer of the subscription
+    pub subscription_signer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = subscription.escrow_token_account @ SubscriptionError::EscrowAccountNotFound
+    )]
+    pub escrow_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = subscription.recipient_token_account @ SubscriptionError::Unauthorized,
+        token::authority = merchant
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    pub merchant: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn handler(ctx: Context<WithdrawStream>) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    require!(subscription.is_active, SubscriptionError::SubscriptionInactive);
+    require!(subscription.stream_rate > 0, SubscriptionError::NotStreaming);
+
+    let now = Clock::get()?.unix_timestamp;
+    pay_out_stream(
+        &mut ctx.accounts.subscription,
+        &ctx.accounts.escrow_token_account,
+        &ctx.accounts.recipient_token_account,
+        &ctx.accounts.subscription_signer,
+        &ctx.accounts.token_program,
+        now,
+    )?;
+
+    Ok(())
+}
+
+/// Pay the merchant everything the stream has accrued up to `now` and
+/// return the amount paid
+pub(crate) fn pay_out_stream<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    escrow_token_account: &Account<'info, TokenAccount>,
+    recipient_token_account: &Account<'info, TokenAccount>,
+    subscription_signer: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    now: i64,
+) -> Result<u64> {
+    let (amount, streamed_until) = billing::stream_accrued(subscription, escrow_token_account.amount, now);
+
+    if amount > 0 {
+        let seeds = &[
+            b"subscription",
+            subscription.user.as_ref(),
+            &[subscription.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                Transfer {
+                    from: escrow_token_account.to_account_info(),
+                    to: recipient_token_account.to_account_info(),
+                    authority: subscription_signer.clone(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+    }
+
+    subscription.streamed_until = streamed_until;
+    subscription.last_payment_timestamp = Some(now);
+    subscription.lifetime_charged = billing::checked_sum([subscription.lifetime_charged, amount])?;
+
+    emit!(StreamWithdrawn {
+        user: subscription.user,
+        recipient: recipient_token_account.key(),
+        amount,
+        streamed_until,
+        timestamp: now,
+    });
+
+    Ok(amount)
+}
+
+#[event]
+pub struct StreamWithdrawn {
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    // Earlier than the timestamp if the escrow ran out
+    pub streamed_until: i64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,219 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
+use crate::error::SubscriptionError;
+
+// Yield adapter for idle USDC escrow. Speaks the SPL token-lending interface,
+// so any compatible reserve (Solend, Port, the local mock reserve) can be
+// plugged in by pointing PlatformConfig at its program and reserve.
+
+// Instruction tags in the token-lending program
+const REFRESH_RESERVE: u8 = 3;
+const DEPOSIT_RESERVE_LIQUIDITY: u8 = 4;
+const REDEEM_RESERVE_COLLATERAL: u8 = 5;
+
+// Reserve layout offsets: version, last_update (slot + stale), lending_market,
+// liquidity { mint, decimals, supply, fee_receiver, oracle, available_amount,
+// borrowed_amount_wads, cumulative_borrow_rate_wads, market_price },
+// collateral { mint, mint_total_supply, supply }
+const LENDING_MARKET_OFFSET: usize = 1 + 8 + 1;
+const LIQUIDITY_SUPPLY_OFFSET: usize = LENDING_MARKET_OFFSET + 32 + 32 + 1;
+const LIQUIDITY_ORACLE_OFFSET: usize = LIQUIDITY_SUPPLY_OFFSET + 32 + 32;
+const AVAILABLE_AMOUNT_OFFSET: usize = LIQUIDITY_ORACLE_OFFSET + 32;
+const BORROWED_WADS_OFFSET: usize = AVAILABLE_AMOUNT_OFFSET + 8;
+const COLLATERAL_MINT_OFFSET: usize = BORROWED_WADS_OFFSET + 16 + 16 + 16;
+const COLLATERAL_SUPPLY_OFFSET: usize = COLLATERAL_MINT_OFFSET + 32;
+
+const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// The parts of a lending reserve the escrow needs
+pub struct ReserveState {
+    pub lending_market: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub liquidity_oracle: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub total_liquidity: u64,
+    pub collateral_supply: u64,
+}
+
+impl ReserveState {
+    /// Collateral that must be redeemed to receive at least `liquidity`
+    pub fn collateral_for(&self, liquidity: u64) -> u64 {
+        if self.total_liquidity == 0 {
+            return liquidity;
+        }
+        let numerator = liquidity as u128 * self.collateral_supply as u128;
+        let denominator = self.total_liquidity as u128;
+        numerator.div_ceil(denominator) as u64
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes: [u8; 32] = data
+        .get(offset..offset + 32)
+        .and_then(|slice| slice.try_into().ok())
+        .ok_or(SubscriptionError::InvalidLendingReserve)?;
+    Ok(Pubkey::new_from_array(bytes))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes: [u8; 8] = data
+        .get(offset..offset + 8)
+        .and_then(|slice| slice.try_into().ok())
+        .ok_or(SubscriptionError::InvalidLendingReserve)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u128(data: &[u8], offset: usize) -> Result<u128> {
+    let bytes: [u8; 16] = data
+        .get(offset..offset + 16)
+        .and_then(|slice| slice.try_into().ok())
+        .ok_or(SubscriptionError::InvalidLendingReserve)?;
+    Ok(u128::from_le_bytes(bytes))
+}
+
+/// Parse raw reserve data; also used off-chain by the keeper
+pub fn parse_reserve(data: &[u8]) -> Result<ReserveState> {
+    let available = read_u64(data, AVAILABLE_AMOUNT_OFFSET)?;
+    let borrowed = read_u128(data, BORROWED_WADS_OFFSET)? / WAD;
+
+    Ok(ReserveState {
+        lending_market: read_pubkey(data, LENDING_MARKET_OFFSET)?,
+        liquidity_supply: read_pubkey(data, LIQUIDITY_SUPPLY_OFFSET)?,
+        liquidity_oracle: read_pubkey(data, LIQUIDITY_ORACLE_OFFSET)?,
+        collateral_mint: read_pubkey(data, COLLATERAL_MINT_OFFSET)?,
+        total_liquidity: available.saturating_add(borrowed as u64),
+        collateral_supply: read_u64(data, COLLATERAL_SUPPLY_OFFSET)?,
+    })
+}
+
+/// Read a reserve account owned by the configured lending program
+pub fn read_reserve(reserve: &AccountInfo, lending_program: &Pubkey) -> Result<ReserveState> {
+    require_keys_eq!(
+        *reserve.owner,
+        *lending_program,
+        SubscriptionError::InvalidLendingReserve
+    );
+    parse_reserve(&reserve.try_borrow_data()?)
+}
+
+/// Refresh the reserve and read it back. The lending program rejects
+/// deposits and redemptions against a reserve not refreshed in the same slot,
+/// and the refresh accrues interest the exchange rate has to include.
+pub fn refresh_and_read_reserve<'info>(
+    reserve: &AccountInfo<'info>,
+    liquidity_oracle: &AccountInfo<'info>,
+    clock: &AccountInfo<'info>,
+    lending_program: &AccountInfo<'info>,
+) -> Result<ReserveState> {
+    let stale = read_reserve(reserve, lending_program.key)?;
+    require_keys_eq!(
+        stale.liquidity_oracle,
+        *liquidity_oracle.key,
+        SubscriptionError::InvalidLendingReserve
+    );
+
+    anchor_lang::solana_program::program::invoke(
+        &refresh_reserve(lending_program.key, reserve.key, &stale),
+        &[reserve.clone(), liquidity_oracle.clone(), clock.clone(), lending_program.clone()],
+    )?;
+
+    read_reserve(reserve, lending_program.key)
+}
+
+/// The lending market's authority PDA
+pub fn market_authority(lending_market: &Pubkey, lending_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[lending_market.as_ref()], lending_program).0
+}
+
+fn data(tag: u8, amount: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(9);
+    data.push(tag);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+/// Accrue the reserve's interest and update its market price
+pub fn refresh_reserve(lending_program: &Pubkey, reserve_key: &Pubkey, reserve: &ReserveState) -> Instruction {
+    Instruction {
+        program_id: *lending_program,
+        accounts: vec![
+            AccountMeta::new(*reserve_key, false),
+            AccountMeta::new_readonly(reserve.liquidity_oracle, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ],
+        data: vec![REFRESH_RESERVE],
+    }
+}
+
+/// Move liquidity into the reserve in exchange for collateral tokens
+pub fn deposit_liquidity(
+    lending_program: &Pubkey,
+    reserve_key: &Pubkey,
+    reserve: &ReserveState,
+    source_liquidity: &Pubkey,
+    destination_collateral: &Pubkey,
+    transfer_authority: &Pubkey,
+    liquidity: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *lending_program,
+        accounts: vec![
+            AccountMeta::new(*source_liquidity, false),
+            AccountMeta::new(*destination_collateral, false),
+            AccountMeta::new(*reserve_key, false),
+            AccountMeta::new(reserve.liquidity_supply, false),
+            AccountMeta::new(reserve.collateral_mint, false),
+            AccountMeta::new_readonly(reserve.lending_market, false),
+            AccountMeta::new_readonly(market_authority(&reserve.lending_market, lending_program), false),
+            AccountMeta::new_readonly(*transfer_authority, true),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ],
+        data: data(DEPOSIT_RESERVE_LIQUIDITY, liquidity),
+    }
+}
+
+/// Burn collateral tokens for liquidity plus accrued interest
+pub fn redeem_collateral(
+    lending_program: &Pubkey,
+    reserve_key: &Pubkey,
+    reserve: &ReserveState,
+    source_collateral: &Pubkey,
+    destination_liquidity: &Pubkey,
+    transfer_authority: &Pubkey,
+    collateral: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *lending_program,
+        accounts: vec![
+            AccountMeta::new(*source_collateral, false),
+            AccountMeta::new(*destination_liquidity, false),
+            AccountMeta::new(*reserve_key, false),
+            AccountMeta::new(reserve.collateral_mint, false),
+            AccountMeta::new(reserve.liquidity_supply, false),
+            AccountMeta::new_readonly(reserve.lending_market, false),
+            AccountMeta::new_readonly(market_authority(&reserve.lending_market, lending_program), false),
+            AccountMeta::new_readonly(*transfer_authority, true),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ],
+        data: data(REDEEM_RESERVE_COLLATERAL, collateral),
+    }
+}
+
+/// Split a redemption into the share of principal it retires and the
+/// interest earned on top. Principal is retired pro rata to collateral, so a
+/// reserve loss shows up as zero interest rather than a bookkeeping gap.
+pub fn split_redemption(
+    lent_principal: u64,
+    lent_collateral: u64,
+    redeemed_collateral: u64,
+    received: u64,
+) -> (u64, u64) {
+    if lent_collateral == 0 {
+        return (0, received);
+    }
+    let principal = (lent_principal as u128 * redeemed_collateral as u128 / lent_collateral as u128) as u64;
+    (principal, received.saturating_sub(principal))
+}
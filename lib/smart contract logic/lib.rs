@@ -1,41 +1,352 @@
+#![allow(clippy::result_large_err)]
+
 use anchor_lang::prelude::*;
 
 pub mod instructions;
 pub mod state;
 pub mod error;
+pub mod oracle;
+pub mod billing;
+pub mod stake_pool;
+pub mod lending;
 
 use instructions::*;
 
-declare_id!("Subscr1pt1on11111111111111111111111111111111");
+declare_id!("Subscr1pt1on1111111111111111111111111111111");
 
 #[program]
 pub mod subscription_manager {
     use super::*;
 
     pub fn initialize_subscription(
-        ctx: Context<initialize_subscription::InitializeSubscription>,
+        ctx: Context<InitializeSubscription>,
         monthly_amount: u64,
         fee_wallet: Pubkey,
     ) -> Result<()> {
         initialize_subscription::handler(ctx, monthly_amount, fee_wallet)
     }
 
-    pub fn process_payment(ctx: Context<process_payment::ProcessPayment>) -> Result<()> {
+    pub fn initialize_delegated_subscription(
+        ctx: Context<InitializeDelegatedSubscription>,
+        monthly_amount: u64,
+        fee_wallet: Pubkey,
+        allowance: u64,
+    ) -> Result<()> {
+        initialize_delegated_subscription::handler(ctx, monthly_amount, fee_wallet, allowance)
+    }
+
+    pub fn process_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPayment<'info>>,
+    ) -> Result<()> {
         process_payment::handler(ctx)
     }
 
-    pub fn cancel_subscription(ctx: Context<cancel_subscription::CancelSubscription>) -> Result<()> {
-        cancel_subscription::handler(ctx)
+    pub fn open_schedule_bucket(
+        ctx: Context<OpenScheduleBucket>,
+        day: i64,
+        page: u16,
+    ) -> Result<()> {
+        open_schedule_bucket::handler(ctx, day, page)
+    }
+
+    pub fn close_schedule_bucket(ctx: Context<CloseScheduleBucket>) -> Result<()> {
+        close_schedule_bucket::handler(ctx)
+    }
+
+    pub fn process_payments_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessPaymentsBatch<'info>>,
+    ) -> Result<()> {
+        process_payments_batch::handler(ctx)
+    }
+
+    pub fn cancel_subscription(
+        ctx: Context<CancelSubscription>,
+        mode: cancel_subscription::CancelMode,
+    ) -> Result<()> {
+        cancel_subscription::handler(ctx, mode)
     }
 
     pub fn update_fee_wallet(
-        ctx: Context<update_fee_wallet::UpdateFeeWallet>,
+        ctx: Context<UpdatePlatformFeeWallet>,
         new_fee_wallet: Pubkey,
     ) -> Result<()> {
-        update_fee_wallet::handler(ctx, new_fee_wallet)
+        update_platform_fee_wallet::handler(ctx, new_fee_wallet)
+    }
+
+    pub fn update_approved_validators(
+        ctx: Context<UpdateApprovedValidators>,
+        approved_validators: Vec<Pubkey>,
+    ) -> Result<()> {
+        update_approved_validators::handler(ctx, approved_validators)
+    }
+
+    pub fn update_stake_pool(
+        ctx: Context<UpdateStakePool>,
+        approved_stake_pool: Pubkey,
+    ) -> Result<()> {
+        update_stake_pool::handler(ctx, approved_stake_pool)
+    }
+
+    pub fn init_platform_config(
+        ctx: Context<InitPlatformConfig>,
+        fee_wallet: Pubkey,
+        price_oracle: Pubkey,
+    ) -> Result<()> {
+        init_platform_config::handler(ctx, fee_wallet, price_oracle)
+    }
+
+    pub fn update_gas_reimbursement(
+        ctx: Context<UpdateGasReimbursement>,
+        gas_reimbursement_lamports: u64,
+        price_oracle: Pubkey,
+    ) -> Result<()> {
+        update_gas_reimbursement::handler(ctx, gas_reimbursement_lamports, price_oracle)
+    }
+
+    pub fn update_keeper_reward(
+        ctx: Context<UpdateKeeperReward>,
+        keeper_reward_bps: u16,
+    ) -> Result<()> {
+        update_keeper_reward::handler(ctx, keeper_reward_bps)
+    }
+
+    pub fn stake_escrow(
+        ctx: Context<StakeEscrow>,
+        stake_lamports: u64,
+    ) -> Result<()> {
+        stake_escrow::handler(ctx, stake_lamports)
+    }
+
+    pub fn deactivate_stake(ctx: Context<DeactivateStake>) -> Result<()> {
+        deactivate_stake::handler(ctx)
+    }
+
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>) -> Result<()> {
+        withdraw_stake::handler(ctx)
+    }
+
+    pub fn deposit_stake_pool(
+        ctx: Context<DepositStakePool>,
+        lamports: u64,
+    ) -> Result<()> {
+        deposit_stake_pool::handler(ctx, lamports)
     }
 
-    pub fn stake_escrow(ctx: Context<stake_escrow::StakeEscrow>) -> Result<()> {
-        stake_escrow::handler(ctx)
+    pub fn redeem_stake_pool_sol(
+        ctx: Context<RedeemStakePoolSol>,
+        pool_tokens: u64,
+    ) -> Result<()> {
+        redeem_stake_pool_sol::handler(ctx, pool_tokens)
+    }
+
+    pub fn redeem_stake_pool_stake(
+        ctx: Context<RedeemStakePoolStake>,
+        pool_tokens: u64,
+    ) -> Result<()> {
+        redeem_stake_pool_stake::handler(ctx, pool_tokens)
+    }
+
+    pub fn update_lending_reserve(
+        ctx: Context<UpdateLendingReserve>,
+        lending_program: Pubkey,
+        lending_reserve: Pubkey,
+    ) -> Result<()> {
+        update_lending_reserve::handler(ctx, lending_program, lending_reserve)
+    }
+
+    pub fn lend_escrow(
+        ctx: Context<LendEscrow>,
+        amount: u64,
+    ) -> Result<()> {
+        lend_escrow::handler(ctx, amount)
+    }
+
+    pub fn withdraw_lent_escrow(ctx: Context<WithdrawLentEscrow>) -> Result<()> {
+        withdraw_lent_escrow::handler(ctx)
+    }
+
+    pub fn update_yield_split(
+        ctx: Context<UpdateYieldSplit>,
+        yield_split: state::YieldSplit,
+    ) -> Result<()> {
+        update_yield_split::handler(ctx, yield_split)
+    }
+
+    pub fn distribute_yield(ctx: Context<DistributeYield>) -> Result<()> {
+        distribute_yield::handler(ctx)
+    }
+
+    pub fn settle_refund_stake(ctx: Context<SettleRefundStake>) -> Result<()> {
+        settle_refund_stake::handler(ctx)
+    }
+
+    pub fn settle_refund_lending(ctx: Context<SettleRefundLending>) -> Result<()> {
+        settle_refund_lending::handler(ctx)
+    }
+
+    pub fn settle_refund_stake_pool(ctx: Context<SettleRefundStakePool>) -> Result<()> {
+        settle_refund_stake_pool::handler(ctx)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_plan(
+        ctx: Context<CreatePlan>,
+        plan_id: u64,
+        price: u64,
+        interval_seconds: i64,
+        prorated: bool,
+        yield_split: Option<state::YieldSplit>,
+        unit_price: u64,
+        setup_fee: u64,
+    ) -> Result<()> {
+        create_plan::handler(ctx, plan_id, price, interval_seconds, prorated, yield_split, unit_price, setup_fee)
+    }
+
+    pub fn finalize_cancellation(ctx: Context<FinalizeCancellation>) -> Result<()> {
+        finalize_cancellation::handler(ctx)
+    }
+
+    pub fn change_plan(
+        ctx: Context<ChangePlan>,
+        defer_downgrade: bool,
+    ) -> Result<()> {
+        change_plan::handler(ctx, defer_downgrade)
+    }
+
+    pub fn update_plan_price(
+        ctx: Context<UpdatePlanPrice>,
+        new_price: u64,
+    ) -> Result<()> {
+        update_plan_price::handler(ctx, new_price)
+    }
+
+    pub fn accept_price_change(ctx: Context<AcceptPriceChange>) -> Result<()> {
+        accept_price_change::handler(ctx)
+    }
+
+    pub fn set_spending_cap(
+        ctx: Context<SetSpendingCap>,
+        max_amount_per_period: u64,
+        lifetime_cap: Option<u64>,
+    ) -> Result<()> {
+        set_spending_cap::handler(ctx, max_amount_per_period, lifetime_cap)
+    }
+
+    pub fn initialize_stream(
+        ctx: Context<InitializeStream>,
+        rate_per_second: u64,
+        fee_wallet: Pubkey,
+    ) -> Result<()> {
+        initialize_stream::handler(ctx, rate_per_second, fee_wallet)
+    }
+
+    pub fn withdraw_stream(ctx: Context<WithdrawStream>) -> Result<()> {
+        withdraw_stream::handler(ctx)
+    }
+
+    pub fn stop_stream(ctx: Context<StopStream>) -> Result<()> {
+        stop_stream::handler(ctx)
+    }
+
+    pub fn report_usage(
+        ctx: Context<ReportUsage>,
+        quantity: u64,
+        record_id: u64,
+    ) -> Result<()> {
+        report_usage::handler(ctx, quantity, record_id)
+    }
+
+    pub fn attach_add_on(
+        ctx: Context<AttachAddOn>,
+        id: u64,
+        price: u64,
+        interval_seconds: i64,
+    ) -> Result<()> {
+        attach_add_on::handler(ctx, id, price, interval_seconds)
+    }
+
+    pub fn detach_add_on(ctx: Context<DetachAddOn>, id: u64) -> Result<()> {
+        detach_add_on::handler(ctx, id)
+    }
+
+    pub fn create_coupon(
+        ctx: Context<CreateCoupon>,
+        code: [u8; 16],
+        discount: state::Discount,
+        duration_periods: u32,
+        max_redemptions: u32,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        create_coupon::handler(ctx, code, discount, duration_periods, max_redemptions, expires_at)
+    }
+
+    pub fn apply_coupon(ctx: Context<ApplyCoupon>) -> Result<()> {
+        apply_coupon::handler(ctx)
+    }
+
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        register_referrer::handler(ctx)
+    }
+
+    pub fn update_referral_program(
+        ctx: Context<UpdateReferralProgram>,
+        referral_share_bps: u16,
+        referral_periods: u32,
+    ) -> Result<()> {
+        update_referral_program::handler(ctx, referral_share_bps, referral_periods)
+    }
+
+    pub fn set_payout_split(
+        ctx: Context<SetPayoutSplit>,
+        payees: Vec<state::Payee>,
+    ) -> Result<()> {
+        set_payout_split::handler(ctx, payees)
+    }
+
+    pub fn update_settlement_delay(
+        ctx: Context<UpdateSettlementDelay>,
+        settlement_delay: i64,
+    ) -> Result<()> {
+        update_settlement_delay::handler(ctx, settlement_delay)
+    }
+
+    pub fn open_settlement(ctx: Context<OpenSettlement>) -> Result<()> {
+        open_settlement::handler(ctx)
+    }
+
+    pub fn claim_settlement<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimSettlement<'info>>,
+    ) -> Result<()> {
+        claim_settlement::handler(ctx)
+    }
+
+    pub fn release_held_payment(ctx: Context<ReleaseHeldPayment>) -> Result<()> {
+        release_held_payment::handler(ctx)
+    }
+
+    pub fn open_dispute(
+        ctx: Context<OpenDispute>,
+        due_at: i64,
+        evidence_uri: String,
+    ) -> Result<()> {
+        open_dispute::handler(ctx, due_at, evidence_uri)
+    }
+
+    pub fn appoint_arbiter(ctx: Context<AppointArbiter>, arbiter: Pubkey) -> Result<()> {
+        appoint_arbiter::handler(ctx, arbiter)
+    }
+
+    pub fn submit_evidence_uri(
+        ctx: Context<SubmitEvidenceUri>,
+        uri: String,
+    ) -> Result<()> {
+        submit_evidence_uri::handler(ctx, uri)
+    }
+
+    pub fn resolve_dispute(
+        ctx: Context<ResolveDispute>,
+        refund_amount: u64,
+    ) -> Result<()> {
+        resolve_dispute::handler(ctx, refund_amount)
     }
 }
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+use crate::error::SubscriptionError;
+
+// Price accounts are Anchor accounts: 8 byte discriminator followed by the price
+const PRICE_OFFSET: usize = 8;
+
+/// Read the SOL price (USDC per SOL, 6 decimals) from a price oracle account
+pub fn read_sol_price(price_oracle: &AccountInfo) -> Result<u64> {
+    let data = price_oracle.try_borrow_data()?;
+    let bytes: [u8; 8] = data
+        .get(PRICE_OFFSET..PRICE_OFFSET + 8)
+        .and_then(|slice| slice.try_into().ok())
+        .ok_or(SubscriptionError::InvalidOraclePrice)?;
+
+    let price = u64::from_le_bytes(bytes);
+    require!(price > 0, SubscriptionError::InvalidOraclePrice);
+    Ok(price)
+}
+
+/// Convert a lamport amount into USDC base units at the given SOL price
+pub fn lamports_to_usdc(lamports: u64, sol_price: u64) -> Result<u64> {
+    let usdc = (lamports as u128)
+        .checked_mul(sol_price as u128)
+        .map(|value| value / LAMPORTS_PER_SOL as u128)
+        .ok_or(SubscriptionError::InvalidAmount)?;
+
+    u64::try_from(usdc).map_err(|_| SubscriptionError::InvalidAmount.into())
+}
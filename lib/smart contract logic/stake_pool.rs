@@ -0,0 +1,180 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::{pubkey, stake, system_program, sysvar};
+use crate::error::SubscriptionError;
+
+/// SPL stake pool program (BlazeStake and most liquid staking pools run on it)
+pub const STAKE_POOL_PROGRAM_ID: Pubkey = pubkey!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+
+/// Exchange rates are stored as lamports per pool token scaled by 1e9
+pub const RATE_SCALE: u128 = 1_000_000_000;
+
+// Instruction tags in the stake pool program
+const WITHDRAW_STAKE: u8 = 10;
+const DEPOSIT_SOL: u8 = 14;
+const WITHDRAW_SOL: u8 = 16;
+
+// Borsh layout offsets in the StakePool account: account_type, manager, staker,
+// stake_deposit_authority, stake_withdraw_bump_seed, validator_list, reserve_stake,
+// pool_mint, manager_fee_account, token_program_id, total_lamports, pool_token_supply
+const VALIDATOR_LIST_OFFSET: usize = 1 + 32 + 32 + 32 + 1;
+const RESERVE_STAKE_OFFSET: usize = VALIDATOR_LIST_OFFSET + 32;
+const POOL_MINT_OFFSET: usize = RESERVE_STAKE_OFFSET + 32;
+const MANAGER_FEE_OFFSET: usize = POOL_MINT_OFFSET + 32;
+const TOTAL_LAMPORTS_OFFSET: usize = MANAGER_FEE_OFFSET + 32 + 32;
+const POOL_TOKEN_SUPPLY_OFFSET: usize = TOTAL_LAMPORTS_OFFSET + 8;
+
+/// The parts of a stake pool's state the escrow needs
+pub struct PoolState {
+    pub validator_list: Pubkey,
+    pub reserve_stake: Pubkey,
+    pub pool_mint: Pubkey,
+    pub manager_fee_account: Pubkey,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+}
+
+impl PoolState {
+    /// Lamports per pool token, scaled by RATE_SCALE
+    pub fn exchange_rate(&self) -> u64 {
+        if self.pool_token_supply == 0 {
+            return RATE_SCALE as u64;
+        }
+        (self.total_lamports as u128 * RATE_SCALE / self.pool_token_supply as u128) as u64
+    }
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes: [u8; 32] = data
+        .get(offset..offset + 32)
+        .and_then(|slice| slice.try_into().ok())
+        .ok_or(SubscriptionError::InvalidStakePool)?;
+    Ok(Pubkey::new_from_array(bytes))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes: [u8; 8] = data
+        .get(offset..offset + 8)
+        .and_then(|slice| slice.try_into().ok())
+        .ok_or(SubscriptionError::InvalidStakePool)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Read the stake pool account owned by the SPL stake pool program
+pub fn read_pool(stake_pool: &AccountInfo) -> Result<PoolState> {
+    require_keys_eq!(
+        *stake_pool.owner,
+        STAKE_POOL_PROGRAM_ID,
+        SubscriptionError::InvalidStakePool
+    );
+    let data = stake_pool.try_borrow_data()?;
+
+    Ok(PoolState {
+        validator_list: read_pubkey(&data, VALIDATOR_LIST_OFFSET)?,
+        reserve_stake: read_pubkey(&data, RESERVE_STAKE_OFFSET)?,
+        pool_mint: read_pubkey(&data, POOL_MINT_OFFSET)?,
+        manager_fee_account: read_pubkey(&data, MANAGER_FEE_OFFSET)?,
+        total_lamports: read_u64(&data, TOTAL_LAMPORTS_OFFSET)?,
+        pool_token_supply: read_u64(&data, POOL_TOKEN_SUPPLY_OFFSET)?,
+    })
+}
+
+/// The pool's withdraw authority PDA
+pub fn withdraw_authority(stake_pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[stake_pool.as_ref(), b"withdraw"], &STAKE_POOL_PROGRAM_ID).0
+}
+
+fn data(tag: u8, amount: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(9);
+    data.push(tag);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+/// Deposit SOL into the pool's reserve in exchange for pool tokens
+pub fn deposit_sol(
+    stake_pool: &Pubkey,
+    pool: &PoolState,
+    lamports_from: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    Instruction {
+        program_id: STAKE_POOL_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(withdraw_authority(stake_pool), false),
+            AccountMeta::new(pool.reserve_stake, false),
+            AccountMeta::new(*lamports_from, true),
+            AccountMeta::new(*pool_tokens_to, false),
+            AccountMeta::new(pool.manager_fee_account, false),
+            // Referral fees go back to the manager
+            AccountMeta::new(pool.manager_fee_account, false),
+            AccountMeta::new(pool.pool_mint, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ],
+        data: data(DEPOSIT_SOL, lamports),
+    }
+}
+
+/// Instant redemption: burn pool tokens for SOL out of the pool's reserve
+pub fn withdraw_sol(
+    stake_pool: &Pubkey,
+    pool: &PoolState,
+    transfer_authority: &Pubkey,
+    pool_tokens_from: &Pubkey,
+    lamports_to: &Pubkey,
+    pool_tokens: u64,
+) -> Instruction {
+    Instruction {
+        program_id: STAKE_POOL_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new_readonly(withdraw_authority(stake_pool), false),
+            AccountMeta::new_readonly(*transfer_authority, true),
+            AccountMeta::new(*pool_tokens_from, false),
+            AccountMeta::new(pool.reserve_stake, false),
+            AccountMeta::new(*lamports_to, false),
+            AccountMeta::new(pool.manager_fee_account, false),
+            AccountMeta::new(pool.pool_mint, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(sysvar::stake_history::ID, false),
+            AccountMeta::new_readonly(stake::program::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ],
+        data: data(WITHDRAW_SOL, pool_tokens),
+    }
+}
+
+/// Delayed redemption: burn pool tokens for an active stake account split off
+/// a validator stake, which then goes through the normal cooldown
+pub fn withdraw_stake(
+    stake_pool: &Pubkey,
+    pool: &PoolState,
+    stake_to_split: &Pubkey,
+    stake_to_receive: &Pubkey,
+    stake_authority: &Pubkey,
+    pool_tokens_from: &Pubkey,
+    pool_tokens: u64,
+) -> Instruction {
+    Instruction {
+        program_id: STAKE_POOL_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*stake_pool, false),
+            AccountMeta::new(pool.validator_list, false),
+            AccountMeta::new_readonly(withdraw_authority(stake_pool), false),
+            AccountMeta::new(*stake_to_split, false),
+            AccountMeta::new(*stake_to_receive, false),
+            AccountMeta::new_readonly(*stake_authority, false),
+            AccountMeta::new_readonly(*stake_authority, true),
+            AccountMeta::new(*pool_tokens_from, false),
+            AccountMeta::new(pool.manager_fee_account, false),
+            AccountMeta::new(pool.pool_mint, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+            AccountMeta::new_readonly(stake::program::ID, false),
+        ],
+        data: data(WITHDRAW_STAKE, pool_tokens),
+    }
+}
@@ -1,7 +1,11 @@
-use anchor_lang::prelude::*;
-
 pub const DEFAULT_FEE: u64 = 10_000_000; // Default fee of $0.10 in smallest USDC unit (10**6 for 6 decimals)
 pub const DECIMALS: u8 = 6; // USDC has 6 decimals
 pub const FEE_BPS: u64 = 100; // Basis points for fee calculation (i.e., 0.10%)
+pub const DEFAULT_GAS_REIMBURSEMENT_LAMPORTS: u64 = 10_000; // SOL cost of a charge transaction repaid to the keeper in USDC
+pub const BPS_DENOMINATOR: u64 = 10_000; // 100% expressed in basis points
+pub const DEFAULT_KEEPER_REWARD_BPS: u16 = 2_000; // Keeper receives 20% of the platform fee per charge
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60; // Width of one payment schedule bucket
+pub const BILLING_INTERVAL_SECONDS: i64 = 30 * SECONDS_PER_DAY; // One billing period (30 days)
+pub const PRICE_CHANGE_NOTICE_SECONDS: i64 = 30 * SECONDS_PER_DAY; // Minimum notice before a plan price increase takes effect
 
 pub const PROGRAM_ID: &str = "YourProgramIdHere"; // Replace with the actual Program ID when deploying
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use crate::state::constants::BPS_DENOMINATOR;
+
+/// A merchant promotion. Subscribers to the merchant redeem it once and get
+/// the discount on their next `duration_periods` charges.
+#[account]
+pub struct Coupon {
+    pub merchant: Pubkey,
+
+    // Merchant-chosen code, zero padded
+    pub code: [u8; 16],
+
+    pub discount: Discount,
+    pub duration_periods: u32,
+
+    // Zero means unlimited
+    pub max_redemptions: u32,
+    pub redemptions: u32,
+
+    // No new redemptions from this time on; subscribers who already redeemed keep the discount
+    pub expires_at: Option<i64>,
+
+    pub bump: u8,
+}
+
+impl Coupon {
+    pub const LEN: usize = 32 + 16 + Discount::LEN + 4 + 4 + 4 + 1 + 8 + 1; // merchant + code + discount + duration_periods + max_redemptions + redemptions + expires_at + bump
+
+    pub fn is_redeemable(&self, now: i64) -> bool {
+        let expired = self.expires_at.is_some_and(|expires_at| now >= expires_at);
+        let exhausted = self.max_redemptions > 0 && self.redemptions >= self.max_redemptions;
+        !expired && !exhausted
+    }
+}
+
+/// Marks a coupon as redeemed on a subscription, at
+/// [b"redemption", coupon, subscription]. Never closed, so a subscriber can't
+/// redeem the same coupon twice even after switching to another one.
+#[account]
+pub struct CouponRedemption {
+    pub coupon: Pubkey,
+    pub subscription: Pubkey,
+    pub redeemed_at: i64,
+    pub bump: u8,
+}
+
+impl CouponRedemption {
+    pub const LEN: usize = 32 + 32 + 8 + 1; // coupon + subscription + redeemed_at + bump
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Discount {
+    PercentOff { bps: u16 },
+    AmountOff { amount: u64 },
+}
+
+impl Discount {
+    pub const LEN: usize = 1 + 8;
+
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            Discount::PercentOff { bps } => bps > 0 && bps as u64 <= BPS_DENOMINATOR,
+            Discount::AmountOff { amount } => amount > 0,
+        }
+    }
+
+    /// Amount taken off `price`, never more than the price itself
+    pub fn off(&self, price: u64) -> u64 {
+        match *self {
+            Discount::PercentOff { bps } => (price as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64,
+            Discount::AmountOff { amount } => amount.min(price),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+/// A subscriber's dispute of one held payment, at
+/// [b"dispute", subscription, due_at]. The payment stays frozen in the
+/// merchant's settlement until the platform's arbiter resolves it.
+#[account]
+pub struct Dispute {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub recipient_token_account: Pubkey,
+
+    // Due date of the period the disputed payment paid for
+    pub due_at: i64,
+    pub amount: u64,
+
+    pub status: DisputeStatus,
+
+    // Latest evidence from each side, e.g. an IPFS or Arweave link
+    pub user_evidence_uri: String,
+    pub merchant_evidence_uri: String,
+
+    // Set on resolution; the rest of `amount` was released to the merchant
+    pub refunded: u64,
+
+    pub opened_at: i64,
+    pub resolved_at: Option<i64>,
+
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeStatus {
+    Open,
+    Resolved,
+}
+
+impl Dispute {
+    pub const MAX_URI_LEN: usize = 200;
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 8 + 1 + (4 + Self::MAX_URI_LEN) * 2 + 8 + 8 + 1 + 8 + 1; // subscription + user + recipient_token_account + due_at + amount + status + evidence uris + refunded + opened_at + resolved_at + bump
+}
@@ -0,0 +1,22 @@
+pub mod constants;
+pub mod platform_config;
+pub mod subscription;
+pub mod schedule_bucket;
+pub mod pending_refund;
+pub mod plan;
+pub mod coupon;
+pub mod referrer;
+pub mod payout_split;
+pub mod settlement;
+pub mod dispute;
+
+pub use platform_config::*;
+pub use subscription::*;
+pub use schedule_bucket::*;
+pub use pending_refund::*;
+pub use plan::*;
+pub use coupon::*;
+pub use referrer::*;
+pub use payout_split::*;
+pub use settlement::*;
+pub use dispute::*;
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use crate::state::constants::BPS_DENOMINATOR;
+
+/// How a merchant's period charges are shared between payees. Keyed by the
+/// merchant's payout token account; while it exists, process_payment pays
+/// the merchant's part of every charge out to the payees instead. Each charge
+/// uses the split in force when it is taken, so a change applies from each
+/// subscription's next period. One-off payments (setup fees, prorated plan
+/// changes, streams) still go to the payout account itself.
+#[account]
+pub struct PayoutSplit {
+    pub recipient_token_account: Pubkey,
+
+    // Owner of the payout account, the only one who may change the split
+    pub authority: Pubkey,
+
+    pub payees: Vec<Payee>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Payee {
+    pub token_account: Pubkey,
+    pub bps: u16,
+}
+
+impl Payee {
+    pub const LEN: usize = 32 + 2;
+}
+
+impl PayoutSplit {
+    pub const MAX_PAYEES: usize = 8;
+    pub const LEN: usize = 32 + 32 + 4 + Payee::LEN * Self::MAX_PAYEES + 1; // recipient_token_account + authority + payees + bump
+
+    pub fn is_valid(payees: &[Payee]) -> bool {
+        !payees.is_empty()
+            && payees.len() <= Self::MAX_PAYEES
+            && payees.iter().all(|payee| payee.bps > 0)
+            && payees.iter().map(|payee| payee.bps as u64).sum::<u64>() == BPS_DENOMINATOR
+    }
+
+    /// Each payee's part of `amount`, in payee order. Shares round down and
+    /// the rounding dust goes to the first payee.
+    pub fn split(&self, amount: u64) -> Vec<u64> {
+        let mut parts: Vec<u64> = self
+            .payees
+            .iter()
+            .map(|payee| (amount as u128 * payee.bps as u128 / BPS_DENOMINATOR as u128) as u64)
+            .collect();
+        let dust = amount - parts.iter().sum::<u64>();
+        if let Some(first) = parts.first_mut() {
+            *first += dust;
+        }
+        parts
+    }
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+/// Funds still owed to a canceled subscriber because they were staked, pooled
+/// or lent when the subscription was canceled. Created by cancel_subscription and
+/// closed once every position has been unwound through the settle_refund_*
+/// instructions, which anyone may call.
+#[account]
+pub struct PendingRefund {
+    pub user: Pubkey,
+    pub subscription: Pubkey,
+
+    // Token account the unwound USDC is refunded to
+    pub destination_token_account: Pubkey,
+
+    // Positions outstanding at cancel time, kept for display
+    pub stake_lamports: u64,
+    pub pool_tokens: u64,
+    pub lent_collateral: u64,
+
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl PendingRefund {
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1; // user + subscription + destination_token_account + stake_lamports + pool_tokens + lent_collateral + created_at + bump
+}
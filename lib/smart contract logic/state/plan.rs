@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use crate::state::YieldSplit;
+
+/// A merchant-defined price point subscribers sign up to. Subscriptions copy
+/// the price and billing terms when they subscribe.
+#[account]
+pub struct Plan {
+    pub merchant: Pubkey,
+
+    // Merchant-chosen id, so one merchant can run several plans
+    pub plan_id: u64,
+
+    // Merchant token account every payment on this plan is sent to
+    pub recipient_token_account: Pubkey,
+
+    // Price per billing period in USDC
+    pub price: u64,
+
+    pub interval_seconds: i64,
+
+    // Charge each period up front, hold it in escrow, and refund the unused
+    // part of the current period on cancel
+    pub prorated: bool,
+
+    // Overrides the platform's yield split for this plan's subscribers
+    pub yield_split: Option<YieldSplit>,
+
+    pub bump: u8,
+
+    // Announced price increase and when it takes effect; zero when none is pending
+    pub pending_price: u64,
+    pub price_effective_at: i64,
+
+    // Metered plans also bill each unit of reported usage at this price; zero for flat plans
+    pub unit_price: u64,
+
+    // Charged once when a subscription to the plan is created; zero for none
+    pub setup_fee: u64,
+}
+
+impl Plan {
+    pub const LEN: usize = 32 + 8 + 32 + 8 + 8 + 1 + 1 + YieldSplit::LEN + 1 + 8 + 8 + 8 + 8; // merchant + plan_id + recipient_token_account + price + interval_seconds + prorated + yield_split + bump + pending_price + price_effective_at + unit_price + setup_fee
+
+    /// Price for a period starting at `timestamp`
+    pub fn price_at(&self, timestamp: i64) -> u64 {
+        if self.pending_price > 0 && timestamp >= self.price_effective_at {
+            self.pending_price
+        } else {
+            self.price
+        }
+    }
+
+    /// Promote an announced increase once it has taken effect
+    pub fn roll_forward(&mut self, now: i64) {
+        if self.pending_price > 0 && now >= self.price_effective_at {
+            self.price = self.pending_price;
+            self.pending_price = 0;
+            self.price_effective_at = 0;
+        }
+    }
+}
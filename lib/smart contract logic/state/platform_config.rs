@@ -1,12 +1,83 @@
 use anchor_lang::prelude::*;
+use crate::state::constants::BPS_DENOMINATOR;
 
 #[account]
 pub struct PlatformConfig {
     pub fee_wallet: Pubkey,
     pub admin: Pubkey,
     pub bump: u8,
+
+    // Price feed used to value SOL gas costs in USDC
+    pub price_oracle: Pubkey,
+
+    // SOL spent by whoever submits a charge, repaid in USDC from escrow
+    pub gas_reimbursement_lamports: u64,
+
+    // Share of the platform fee paid to the keeper that cranks a payment
+    pub keeper_reward_bps: u16,
+
+    // Vote accounts escrowed SOL may be delegated to
+    pub approved_validators: Vec<Pubkey>,
+
+    // SPL stake pool escrowed SOL may be liquid staked in
+    pub approved_stake_pool: Pubkey,
+
+    // SPL token-lending program and reserve idle USDC escrow may be lent to
+    pub lending_program: Pubkey,
+    pub lending_reserve: Pubkey,
+
+    // How realized escrow yield is shared out
+    pub yield_split: YieldSplit,
+
+    // Share of the platform's part of the fee paid to a subscription's
+    // referrer, for that many of the subscription's first charges
+    pub referral_share_bps: u16,
+    pub referral_periods: u32,
+
+    // Seconds each charge is held in the merchant's settlement before it can
+    // be claimed, during which the subscriber may dispute it; 0 pays merchants directly
+    pub settlement_delay: i64,
+
+    // Resolves disputed settlements; nobody until the admin appoints one
+    pub arbiter: Pubkey,
+}
+
+/// Shares of realized escrow yield in basis points, summing to 100%
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct YieldSplit {
+    // Left in escrow, extending the subscriber's runway
+    pub subscriber_bps: u16,
+
+    // Paid to the merchant, subsidizing the subscriber's price
+    pub merchant_bps: u16,
+
+    // Swept to the platform fee wallet
+    pub platform_bps: u16,
+}
+
+impl YieldSplit {
+    pub const LEN: usize = 2 + 2 + 2;
+
+    /// All yield stays with the subscriber
+    pub const SUBSCRIBER_ONLY: YieldSplit = YieldSplit {
+        subscriber_bps: BPS_DENOMINATOR as u16,
+        merchant_bps: 0,
+        platform_bps: 0,
+    };
+
+    pub fn is_valid(&self) -> bool {
+        self.subscriber_bps as u64 + self.merchant_bps as u64 + self.platform_bps as u64 == BPS_DENOMINATOR
+    }
+
+    /// Split `amount` into (subscriber, merchant, platform); rounding dust stays with the subscriber
+    pub fn apply(&self, amount: u64) -> (u64, u64, u64) {
+        let merchant = (amount as u128 * self.merchant_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        let platform = (amount as u128 * self.platform_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        (amount - merchant - platform, merchant, platform)
+    }
 }
 
 impl PlatformConfig {
-    pub const LEN: usize = 32 + 32 + 1; // fee_wallet + admin + bump
+    pub const MAX_VALIDATORS: usize = 8;
+    pub const LEN: usize = 32 + 32 + 1 + 32 + 8 + 2 + 4 + 32 * Self::MAX_VALIDATORS + 32 + 32 + 32 + YieldSplit::LEN + 2 + 4 + 8 + 32; // fee_wallet + admin + bump + price_oracle + gas_reimbursement_lamports + keeper_reward_bps + approved_validators + approved_stake_pool + lending_program + lending_reserve + yield_split + referral_share_bps + referral_periods + settlement_delay + arbiter
 }
@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+/// Running totals for one referrer, who earns a share of the platform fee on
+/// the first periods of every subscription they referred
+#[account]
+pub struct Referrer {
+    // Referral shares are paid to this wallet's USDC associated token account
+    pub wallet: Pubkey,
+
+    pub referred_subscriptions: u32,
+    pub periods_paid: u64,
+    pub total_earned: u64,
+
+    pub bump: u8,
+}
+
+impl Referrer {
+    pub const LEN: usize = 32 + 4 + 8 + 8 + 1; // wallet + referred_subscriptions + periods_paid + total_earned + bump
+}
@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use crate::error::SubscriptionError;
+use crate::state::Subscription;
+use crate::state::constants::SECONDS_PER_DAY;
+
+/// One page of the on-chain payment schedule: every subscription whose
+/// `next_payment_due` falls on `day`. Days with more subscriptions than a
+/// page holds spill over into pages 1, 2, ... so a keeper can fetch
+/// `[b"schedule", day, page]` for increasing pages until one is missing.
+#[account]
+pub struct ScheduleBucket {
+    // Days since the Unix epoch covered by this bucket
+    pub day: i64,
+
+    // Page index within the day
+    pub page: u16,
+
+    pub bump: u8,
+
+    // Paid the page's rent; refunded when the emptied page is closed
+    pub payer: Pubkey,
+
+    // Subscription accounts due on this day
+    pub subscriptions: Vec<Pubkey>,
+}
+
+impl ScheduleBucket {
+    pub const MAX_SUBSCRIPTIONS: usize = 64;
+    pub const LEN: usize = 8 + 2 + 1 + 32 + 4 + 32 * Self::MAX_SUBSCRIPTIONS; // day + page + bump + payer + vec prefix + entries
+
+    // Day index of a unix timestamp
+    pub fn day_of(timestamp: i64) -> i64 {
+        timestamp.div_euclid(SECONDS_PER_DAY)
+    }
+
+    // Whether this is the page a subscription is currently filed under
+    pub fn holds(&self, subscription: &Subscription) -> bool {
+        self.day == Self::day_of(subscription.next_payment_due)
+            && self.page == subscription.schedule_page
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.subscriptions.len() >= Self::MAX_SUBSCRIPTIONS
+    }
+
+    // File a subscription on this page; a key already filed here is left as is
+    pub fn insert(&mut self, subscription: Pubkey) -> Result<()> {
+        if self.subscriptions.contains(&subscription) {
+            return Ok(());
+        }
+        require!(!self.is_full(), SubscriptionError::ScheduleBucketFull);
+        self.subscriptions.push(subscription);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, subscription: &Pubkey) {
+        if let Some(index) = self.subscriptions.iter().position(|key| key == subscription) {
+            self.subscriptions.swap_remove(index);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+use crate::state::PayoutSplit;
+use crate::error::SubscriptionError;
+
+/// A merchant's pending balance while the platform holds settlements. Keyed
+/// by the merchant's payout token account; charges pay the merchant's part
+/// into the settlement vault at [b"settlement-vault", recipient] instead, and
+/// each payment is recorded in its own HeldPayment until its release time so
+/// the subscriber can dispute it. claim_settlement pays out everything
+/// released so far, to the payees recorded with each payment when it was
+/// charged. One-off payments (setup fees, prorated plan changes,
+/// streams) are not held.
+#[account]
+pub struct Settlement {
+    pub recipient_token_account: Pubkey,
+
+    // Released payments not yet claimed by the merchant, and who they are owed to
+    pub released: u64,
+    pub payouts: Vec<Payout>,
+
+    // Total of payments still inside their dispute window, or frozen by a dispute
+    pub held: u64,
+
+    pub bump: u8,
+}
+
+/// A payee's part of a held payment, fixed when the payment is charged
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Payout {
+    pub token_account: Pubkey,
+    pub amount: u64,
+}
+
+impl Payout {
+    pub const LEN: usize = 32 + 8;
+}
+
+impl Settlement {
+    // Distinct payees owed between claims
+    pub const MAX_PAYOUTS: usize = 2 * PayoutSplit::MAX_PAYEES;
+    pub const LEN: usize = 32 + 8 + 4 + Payout::LEN * Self::MAX_PAYOUTS + 8 + 1; // recipient_token_account + released + payouts + held + bump
+
+    /// Take a held payment of `amount` out of the held total and add
+    /// `payouts`, what is left of it after any refund, to the claimable balance
+    pub fn release(&mut self, amount: u64, payouts: &[Payout]) -> Result<()> {
+        self.held = self.held.checked_sub(amount).ok_or(SubscriptionError::MathOverflow)?;
+        for payout in payouts.iter().filter(|payout| payout.amount > 0) {
+            self.released = self.released.checked_add(payout.amount).ok_or(SubscriptionError::MathOverflow)?;
+            match self.payouts.iter_mut().find(|owed| owed.token_account == payout.token_account) {
+                Some(owed) => owed.amount = owed.amount.checked_add(payout.amount).ok_or(SubscriptionError::MathOverflow)?,
+                None => {
+                    require!(self.payouts.len() < Self::MAX_PAYOUTS, SubscriptionError::SettlementFull);
+                    self.payouts.push(*payout);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One charge held in a merchant's settlement, at
+/// [b"held", recipient_token_account, subscription, due_at]. Closed back to
+/// whoever paid its rent once released or resolved.
+#[account]
+pub struct HeldPayment {
+    pub recipient_token_account: Pubkey,
+
+    // The subscriber who paid, and the due date of the period they paid for
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub due_at: i64,
+
+    pub amount: u64,
+    pub release_at: i64,
+
+    // How `amount` is shared between payees, from the payout split when charged
+    pub payouts: Vec<Payout>,
+
+    // Disputed payments are frozen past release_at until the arbiter resolves the dispute
+    pub disputed: bool,
+
+    // The keeper whose charge opened this record
+    pub payer: Pubkey,
+
+    pub bump: u8,
+}
+
+impl HeldPayment {
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 8 + 8 + 4 + Payout::LEN * PayoutSplit::MAX_PAYEES + 1 + 32 + 1; // recipient_token_account + subscription + user + due_at + amount + release_at + payouts + disputed + payer + bump
+
+    /// The payouts for `released` of this payment, scaled down from the
+    /// recorded ones when part of it was refunded. Shares round down and the
+    /// rounding dust goes to the first payee.
+    pub fn payouts_of(&self, released: u64) -> Vec<Payout> {
+        let mut payouts: Vec<Payout> = self
+            .payouts
+            .iter()
+            .map(|payout| Payout {
+                token_account: payout.token_account,
+                amount: (payout.amount as u128 * released as u128 / self.amount.max(1) as u128) as u64,
+            })
+            .collect();
+        let dust = released - payouts.iter().map(|payout| payout.amount).sum::<u64>();
+        if let Some(first) = payouts.first_mut() {
+            first.amount += dust;
+        }
+        payouts
+    }
+}
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
+use crate::state::constants::BILLING_INTERVAL_SECONDS;
+use crate::state::Discount;
 
 #[account]
 pub struct Subscription {
@@ -7,7 +8,6 @@ pub struct Subscription {
     pub user: Pubkey,
 
     // The escrow account holding the USDC for the subscription
-    #[account(mut)]
     pub escrow_token_account: Pubkey,
 
     // The amount of USDC for the subscription (per month)
@@ -30,15 +30,216 @@ pub struct Subscription {
 
     // Optionally, store staked SOL balance if staking is enabled
     pub staked_balance: Option<u64>,
+
+    // The merchant token account every payment is sent to
+    pub recipient_token_account: Pubkey,
+
+    // Length of one billing period in seconds
+    pub interval_seconds: i64,
+
+    // Earliest time a keeper may charge the next payment
+    pub next_payment_due: i64,
+
+    // Page of the schedule bucket for next_payment_due's day this subscription is filed under
+    pub schedule_page: u16,
+
+    // Native stake account holding escrowed SOL, if any
+    pub stake_account: Option<Pubkey>,
+
+    // Lamports delegated to the validator (excludes the stake account's rent reserve)
+    pub stake_delegated_amount: u64,
+
+    // Epoch the stake was delegated in
+    pub stake_activation_epoch: Option<u64>,
+
+    // Epoch deactivation was requested in; withdrawable once this epoch has passed
+    pub stake_deactivation_epoch: Option<u64>,
+
+    // Stake pool tokens held in the subscription's pool-token vault. Bought
+    // with the user's SOL, so they back refunds but never USDC charges.
+    pub pool_token_balance: u64,
+
+    // Lamports per pool token (scaled by 1e9) as of the last deposit or redemption
+    pub pool_exchange_rate: u64,
+
+    // USDC deposited into the lending reserve and not yet withdrawn
+    pub lent_principal: u64,
+
+    // Reserve collateral tokens held in the subscription's collateral vault
+    pub lent_collateral: u64,
+
+    // Interest realized into escrow by redemptions and not yet distributed
+    pub accrued_interest: u64,
+
+    // Subscriber's share of distributed yield, left in escrow as extra runway
+    pub yield_credited: u64,
+
+    // Set by an at-period-end cancel: no charges are taken, and the escrow is
+    // refunded to refund_destination once this time is reached
+    pub cancel_at: Option<i64>,
+    pub refund_destination: Option<Pubkey>,
+
+    // Plan the subscription was created from, if any
+    pub plan: Option<Pubkey>,
+
+    // Prorated plans charge up front and hold the period's payment in escrow
+    // until the period is used up
+    pub prorated: bool,
+    pub held_amount: u64,
+
+    // Owed to the subscriber from a mid-period downgrade, taken off the next charges
+    pub credit: u64,
+
+    // Downgrade deferred to the next period, applied when that period is charged
+    pub pending_plan: Option<Pubkey>,
+    pub pending_price: u64,
+    pub pending_unit_price: u64,
+
+    // Announced plan price increase the subscriber agreed to; cleared once it
+    // applies or the subscription changes plan
+    pub accepted_price: Option<AcceptedPrice>,
+
+    // Subscriber-set ceiling on everything taken in one period, fees included; zero means uncapped
+    pub max_amount_per_period: u64,
+
+    // Optional subscriber-set ceiling on everything ever taken
+    pub lifetime_cap: Option<u64>,
+
+    // Taken so far in the current period and over the subscription's life
+    pub period_charged: u64,
+    pub lifetime_charged: u64,
+
+    // Delegated subscriptions hold no escrow: escrow_token_account is the
+    // user's own token account, which has approved the subscription PDA to
+    // pull each charge
+    pub delegated: bool,
+
+    // Set when a delegated charge finds too little allowance or balance;
+    // cleared by the next successful charge
+    pub past_due: bool,
+
+    // Streams pay the merchant continuously at this many tokens per second
+    // instead of being charged per period; zero for billed subscriptions
+    pub stream_rate: u64,
+
+    // The stream has been paid out to the merchant up to this time
+    pub streamed_until: i64,
+
+    // Metered plans: usage the merchant reported since the last charge, billed
+    // with the next charge at the plan's unit price
+    pub unit_price: u64,
+    pub usage_units: u64,
+
+    // Recurring extras billed alongside the main charge
+    pub add_ons: Vec<AddOn>,
+
+    // Redeemed coupon and its discount, applied to the next coupon_periods_left charges
+    pub coupon: Option<Pubkey>,
+    pub coupon_discount: Option<Discount>,
+    pub coupon_periods_left: u32,
+
+    // Wallet that referred the subscriber, and how many charges have paid it a share
+    pub referrer: Option<Pubkey>,
+    pub referral_periods_paid: u32,
+}
+
+/// A line item attached to a subscription with its own price and interval.
+/// Every period of it that has come due is billed with the next main charge.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct AddOn {
+    // Merchant-chosen id, unique within the subscription
+    pub id: u64,
+    pub price: u64,
+    pub interval_seconds: i64,
+
+    // Start of the add-on's next unbilled period
+    pub next_due: i64,
+}
+
+impl AddOn {
+    pub const LEN: usize = 8 + 8 + 8 + 8; // id + price + interval_seconds + next_due
+
+    /// Number of periods that have started by `now` and not been billed
+    pub fn periods_due(&self, now: i64) -> u64 {
+        if now < self.next_due {
+            return 0;
+        }
+        ((now - self.next_due) / self.interval_seconds + 1) as u64
+    }
+}
+
+/// A plan price increase the subscriber agreed to, as announced
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct AcceptedPrice {
+    pub plan: Pubkey,
+    pub price: u64,
+    pub effective_at: i64,
+}
+
+impl AcceptedPrice {
+    pub const LEN: usize = 32 + 8 + 8; // plan + price + effective_at
 }
 
 impl Subscription {
+    pub const MAX_ADD_ONS: usize = 4;
+    pub const LEN: usize = 32 // user
+        + 32 // escrow_token_account
+        + 8 // monthly_amount
+        + 1 // is_active
+        + 1 // bump
+        + 32 // fee_wallet
+        + 1 + 8 // last_payment_timestamp
+        + 1 + 8 // expiration_timestamp
+        + 1 + 8 // staked_balance
+        + 32 // recipient_token_account
+        + 8 // interval_seconds
+        + 8 // next_payment_due
+        + 2 // schedule_page
+        + 1 + 32 // stake_account
+        + 8 // stake_delegated_amount
+        + 1 + 8 // stake_activation_epoch
+        + 1 + 8 // stake_deactivation_epoch
+        + 8 // pool_token_balance
+        + 8 // pool_exchange_rate
+        + 8 // lent_principal
+        + 8 // lent_collateral
+        + 8 // accrued_interest
+        + 8 // yield_credited
+        + 1 + 8 // cancel_at
+        + 1 + 32 // refund_destination
+        + 1 + 32 // plan
+        + 1 // prorated
+        + 8 // held_amount
+        + 8 // credit
+        + 1 + 32 // pending_plan
+        + 8 // pending_price
+        + 8 // pending_unit_price
+        + 1 + AcceptedPrice::LEN // accepted_price
+        + 8 // max_amount_per_period
+        + 1 + 8 // lifetime_cap
+        + 8 // period_charged
+        + 8 // lifetime_charged
+        + 1 // delegated
+        + 1 // past_due
+        + 8 // stream_rate
+        + 8 // streamed_until
+        + 8 // unit_price
+        + 8 // usage_units
+        + 4 + AddOn::LEN * Self::MAX_ADD_ONS // add_ons
+        + 1 + 32 // coupon
+        + 1 + Discount::LEN // coupon_discount
+        + 4 // coupon_periods_left
+        + 1 + 32 // referrer
+        + 4; // referral_periods_paid
+
     // Initializes a new Subscription account with given parameters
     pub fn new(
         user: Pubkey,
         escrow_token_account: Pubkey,
         monthly_amount: u64,
         fee_wallet: Pubkey,
+        recipient_token_account: Pubkey,
+        next_payment_due: i64,
         bump: u8,
     ) -> Self {
         Subscription {
@@ -51,6 +252,51 @@ impl Subscription {
             last_payment_timestamp: None,
             expiration_timestamp: None,
             staked_balance: None,
+            recipient_token_account,
+            interval_seconds: BILLING_INTERVAL_SECONDS,
+            next_payment_due,
+            schedule_page: 0,
+            stake_account: None,
+            stake_delegated_amount: 0,
+            stake_activation_epoch: None,
+            stake_deactivation_epoch: None,
+            pool_token_balance: 0,
+            pool_exchange_rate: 0,
+            lent_principal: 0,
+            lent_collateral: 0,
+            accrued_interest: 0,
+            yield_credited: 0,
+            cancel_at: None,
+            refund_destination: None,
+            plan: None,
+            prorated: false,
+            held_amount: 0,
+            credit: 0,
+            pending_plan: None,
+            pending_price: 0,
+            pending_unit_price: 0,
+            accepted_price: None,
+            max_amount_per_period: 0,
+            lifetime_cap: None,
+            period_charged: 0,
+            lifetime_charged: 0,
+            delegated: false,
+            past_due: false,
+            stream_rate: 0,
+            streamed_until: 0,
+            unit_price: 0,
+            usage_units: 0,
+            add_ons: Vec::new(),
+            coupon: None,
+            coupon_discount: None,
+            coupon_periods_left: 0,
+            referrer: None,
+            referral_periods_paid: 0,
         }
     }
+
+    // Whether any escrow is staked, pooled or lent rather than sitting in the escrow token account
+    pub fn has_illiquid_positions(&self) -> bool {
+        self.stake_account.is_some() || self.pool_token_balance > 0 || self.lent_collateral > 0
+    }
 }
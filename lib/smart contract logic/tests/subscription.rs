@@ -1,235 +1,1674 @@
-use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-use solana_program::pubkey::Pubkey;
-use anchor_spl::associated_token::AssociatedToken;
-use crate::program::SubscriptionProgram;
-use crate::state::{Subscription, platform_config::PlatformConfig};
-use crate::error::SubscriptionError;
-use crate::utils::{check_authority, is_subscription_owner, validate_subscription_initialized};
-use crate::instruction::{init_subscription, process_payment, cancel_subscription, update_fee_wallet};
-use anchor_lang::solana_program::system_program;
-use anchor_spl::token::Mint;
-use solana_program_test::{ProgramTest, processor};
-use solana_sdk::{signature::{Keypair,Signer}, transaction::Transaction, commitment_config::CommitmentConfig, pubkey::Pubkey as SolanaPubkey};
-
-#[derive(Debug)]
-pub struct TestContext {
-    pub program_test: ProgramTest,
-    pub user: Keypair,
-    pub platform_admin: Keypair,
-    pub subscription_pda: SolanaPubkey,
-    pub fee_wallet: SolanaPubkey,
-    pub mint: Keypair,
-    pub token_program: Pubkey,
-}
-
-impl TestContext {
-    fn new() -> Self {
-        let program_test = ProgramTest::new(
-            "subscription_program",
-            crate::ID,
-            processor!(SubscriptionProgram::process_instruction),
+// Runs the program natively inside solana-program-test, so neither a
+// validator nor `cargo build-sbf` is needed. The oracle is a plain account in
+// the adapter's layout, and the clock is moved forward by overwriting the
+// Clock sysvar. Staking, stake pool and lending flows need those external
+// programs deployed and aren't covered here.
+
+use anchor_lang::prelude::{AccountInfo, Clock, Pubkey};
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token::spl_token;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::account::{Account, AccountSharedData};
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use subscription_manager::error::SubscriptionError;
+use subscription_manager::instructions::CancelMode;
+use subscription_manager::state::constants::{BILLING_INTERVAL_SECONDS, SECONDS_PER_DAY};
+use subscription_manager::state::{
+    Discount, Dispute, HeldPayment, Payee, PlatformConfig, ScheduleBucket, Settlement, Subscription,
+};
+use subscription_manager::{accounts, instruction};
+
+const MONTHLY_AMOUNT: u64 = 10 * 1_000_000; // 10 USDC
+const SOL_PRICE: u64 = 50 * 1_000_000; // 50 USDC per SOL
+
+// Per-charge amounts with the platform defaults: a 0.01 USDC fee of which the
+// keeper earns 20%, and 10_000 lamports of gas at SOL_PRICE
+const FEE: u64 = 10_000;
+const KEEPER_REWARD: u64 = 2_000;
+const GAS: u64 = 500;
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entrypoint wants the account infos to outlive the call
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    subscription_manager::entry(program_id, accounts, data)
+}
+
+fn program_id() -> Pubkey {
+    subscription_manager::ID
+}
+
+fn ix(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: program_id(),
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &program_id()).0
+}
+
+fn subscription_pda(user: &Pubkey) -> Pubkey {
+    pda(&[b"subscription", user.as_ref()])
+}
+
+fn platform_config_pda() -> Pubkey {
+    pda(&[b"platform-config"])
+}
+
+fn schedule_bucket_pda(day: i64, page: u16) -> Pubkey {
+    pda(&[b"schedule", &day.to_le_bytes(), &page.to_le_bytes()])
+}
+
+fn payout_split_pda(recipient: &Pubkey) -> Pubkey {
+    pda(&[b"payout-split", recipient.as_ref()])
+}
+
+fn coupon_redemption_pda(coupon: &Pubkey, subscription: &Pubkey) -> Pubkey {
+    pda(&[b"redemption", coupon.as_ref(), subscription.as_ref()])
+}
+
+/// The custom error code a failed transaction returned
+fn error_code(result: Result<(), BanksClientError>) -> u32 {
+    match result.expect_err("transaction should fail").unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        other => panic!("unexpected error {other:?}"),
+    }
+}
+
+fn assert_error(result: Result<(), BanksClientError>, error: SubscriptionError) {
+    assert_eq!(error_code(result), u32::from(error));
+}
+
+/// A merchant's wallet and its USDC payout account
+struct Merchant {
+    wallet: Keypair,
+    recipient: Pubkey,
+}
+
+/// A subscriber's wallet, their subscription and its escrow
+struct Subscriber {
+    user: Keypair,
+    wallet_token_account: Pubkey,
+    subscription: Pubkey,
+    escrow: Pubkey,
+    recipient: Pubkey,
+}
+
+/// Optional accounts a subscription is created with
+#[derive(Default)]
+struct Terms {
+    plan: Option<Pubkey>,
+    coupon: Option<Pubkey>,
+    referrer: Option<Pubkey>,
+}
+
+/// Billing terms of a merchant plan beyond its price
+#[derive(Default)]
+struct PlanTerms {
+    prorated: bool,
+    unit_price: u64,
+    setup_fee: u64,
+}
+
+/// Optional accounts a charge is submitted with
+#[derive(Default)]
+struct ChargeExtras {
+    plan: Option<Pubkey>,
+    referrer: Option<(Pubkey, Pubkey)>,
+    settlement: bool,
+    payees: Vec<Pubkey>,
+}
+
+struct Test {
+    context: ProgramTestContext,
+    mint: Pubkey,
+    fee_wallet: Keypair,
+    fee_wallet_token_account: Pubkey,
+    keeper_token_account: Pubkey,
+}
+
+impl Test {
+    async fn new() -> Self {
+        let price_oracle = Pubkey::new_unique();
+        let mut oracle_data = vec![0u8; 8];
+        oracle_data.extend_from_slice(&SOL_PRICE.to_le_bytes());
+
+        let mut program_test = ProgramTest::new("subscription_manager", program_id(), processor!(process_instruction));
+        program_test.add_account(
+            price_oracle,
+            Account {
+                lamports: 1_000_000_000,
+                data: oracle_data,
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            },
         );
+        let context = program_test.start_with_context().await;
 
-        let user = Keypair::new();
-        let platform_admin = Keypair::new();
-        let fee_wallet = platform_admin.pubkey();
-        let mint = Keypair::new();
-        let token_program = anchor_spl::token::ID;
+        let mut test = Test {
+            context,
+            mint: Pubkey::new_unique(),
+            fee_wallet: Keypair::new(),
+            fee_wallet_token_account: Pubkey::default(),
+            keeper_token_account: Pubkey::default(),
+        };
+
+        let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: Some(test.payer()).into(),
+            supply: u64::MAX / 2,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: None.into(),
+        }
+        .pack_into_slice(&mut mint_data);
+        test.set_account(test.mint, spl_token::ID, mint_data);
+
+        test.fee_wallet_token_account = test.token_account(&test.fee_wallet.pubkey(), 0);
+        test.keeper_token_account = test.token_account(&test.payer(), 0);
+
+        let admin = test.payer();
+        test.send(
+            &[ix(
+                accounts::InitPlatformConfig {
+                    platform_config: platform_config_pda(),
+                    admin,
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::InitPlatformConfig {
+                    fee_wallet: test.fee_wallet.pubkey(),
+                    price_oracle,
+                },
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+        test
+    }
+
+    fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    fn set_account(&mut self, address: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        let lamports = 1_000_000_000;
+        self.context.set_account(
+            &address,
+            &AccountSharedData::from(Account {
+                lamports,
+                data,
+                owner,
+                executable: false,
+                rent_epoch: 0,
+            }),
+        );
+    }
+
+    /// Write a USDC token account at `owner`'s associated address
+    fn token_account(&mut self, owner: &Pubkey, amount: u64) -> Pubkey {
+        let address = get_associated_token_address(owner, &self.mint);
+        let mut data = vec![0u8; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: self.mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        self.set_account(address, spl_token::ID, data);
+        address
+    }
+
+    async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), BanksClientError> {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&self.payer()), &all_signers, blockhash);
+        self.context.banks_client.process_transaction(tx).await
+    }
+
+    async fn now(&mut self) -> i64 {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+    }
+
+    /// Move the clock to `timestamp`
+    async fn warp_to(&mut self, timestamp: i64) {
+        let mut clock = self.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        self.context.warp_to_slot(clock.slot + 1).unwrap();
+        clock.slot += 1;
+        clock.unix_timestamp = timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    async fn balance(&mut self, token_account: Pubkey) -> u64 {
+        let account = self.context.banks_client.get_account(token_account).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    async fn lamports(&mut self, address: Pubkey) -> u64 {
+        self.context.banks_client.get_balance(address).await.unwrap()
+    }
+
+    async fn exists(&mut self, address: Pubkey) -> bool {
+        self.context.banks_client.get_account(address).await.unwrap().is_some()
+    }
+
+    async fn fetch<T: AccountDeserialize>(&mut self, address: Pubkey) -> T {
+        let account = self.context.banks_client.get_account(address).await.unwrap().unwrap();
+        T::try_deserialize(&mut &account.data[..]).unwrap()
+    }
 
-        let subscription_pda = Pubkey::find_program_address(&[b"subscription", user.pubkey().as_ref()], &crate::ID).0;
+    async fn subscription(&mut self, subscriber: &Subscriber) -> Subscription {
+        self.fetch(subscriber.subscription).await
+    }
+
+    async fn fund(&mut self, wallet: &Pubkey) {
+        let payer = self.payer();
+        self.send(&[system_instruction::transfer(&payer, wallet, 1_000_000_000)], &[])
+            .await
+            .unwrap();
+    }
+
+    /// Page 0 of the schedule for the day `timestamp` falls on, opened by the
+    /// test payer if nobody has yet
+    async fn schedule_bucket(&mut self, timestamp: i64) -> Pubkey {
+        let day = ScheduleBucket::day_of(timestamp);
+        let schedule_bucket = schedule_bucket_pda(day, 0);
+        if !self.exists(schedule_bucket).await {
+            let payer = self.payer();
+            self.send(
+                &[ix(
+                    accounts::OpenScheduleBucket {
+                        schedule_bucket,
+                        payer,
+                        system_program: solana_sdk::system_program::ID,
+                    },
+                    instruction::OpenScheduleBucket { day, page: 0 },
+                )],
+                &[],
+            )
+            .await
+            .unwrap();
+        }
+        schedule_bucket
+    }
+
+    async fn merchant(&mut self) -> Merchant {
+        let wallet = Keypair::new();
+        self.fund(&wallet.pubkey()).await;
+        let recipient = self.token_account(&wallet.pubkey(), 0);
+        Merchant { wallet, recipient }
+    }
+
+    /// A fresh user subscribed to `merchant`, with `deposit` in escrow
+    async fn subscribe(&mut self, merchant: &Merchant, monthly_amount: u64, deposit: u64, terms: Terms) -> Subscriber {
+        let subscriber = self.subscriber(merchant, deposit).await;
+        let result = self.initialize(&subscriber, monthly_amount, terms).await;
+        result.unwrap();
+        subscriber
+    }
 
-        TestContext {
-            program_test,
+    /// A user with a funded escrow who hasn't subscribed yet
+    async fn subscriber(&mut self, merchant: &Merchant, deposit: u64) -> Subscriber {
+        let user = Keypair::new();
+        self.fund(&user.pubkey()).await;
+        let subscription = subscription_pda(&user.pubkey());
+        Subscriber {
+            wallet_token_account: self.token_account(&user.pubkey(), 0),
+            escrow: self.token_account(&subscription, deposit),
+            recipient: merchant.recipient,
+            subscription,
             user,
-            platform_admin,
-            subscription_pda,
-            fee_wallet,
-            mint,
-            token_program,
         }
     }
 
-    fn create_accounts(&self) {
-        let mut context = self.program_test.start_with_context().unwrap();
+    async fn initialize(&mut self, subscriber: &Subscriber, monthly_amount: u64, terms: Terms) -> Result<(), BanksClientError> {
+        let now = self.now().await;
+        let schedule_bucket = self.schedule_bucket(now).await;
+        let fee_wallet = self.fee_wallet.pubkey();
+        self.send(
+            &[ix(
+                accounts::InitializeSubscription {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                    fee_wallet,
+                    escrow_token_account: subscriber.escrow,
+                    recipient_token_account: subscriber.recipient,
+                    schedule_bucket,
+                    plan: terms.plan,
+                    coupon: terms.coupon,
+                    coupon_redemption: terms.coupon.map(|coupon| coupon_redemption_pda(&coupon, &subscriber.subscription)),
+                    referrer: terms.referrer,
+                    token_program: spl_token::ID,
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::InitializeSubscription { monthly_amount, fee_wallet },
+            )],
+            &[&subscriber.user],
+        )
+        .await
+    }
 
-        let user_token_account = self.create_associated_token_account(&mut context, &self.user, &self.mint.pubkey());
-        let fee_wallet_account = self.create_associated_token_account(&mut context, &self.platform_admin, &self.mint.pubkey());
-        let escrow_account = self.create_associated_token_account(&mut context, &self.user, &self.mint.pubkey());
+    /// The current and next schedule pages of a subscription's due charge
+    async fn charge_buckets(&mut self, subscriber: &Subscriber) -> (Pubkey, Pubkey) {
+        let subscription = self.subscription(subscriber).await;
+        let due_bucket = schedule_bucket_pda(
+            ScheduleBucket::day_of(subscription.next_payment_due),
+            subscription.schedule_page,
+        );
+        let next_bucket = self
+            .schedule_bucket(subscription.next_payment_due + subscription.interval_seconds)
+            .await;
+        (due_bucket, next_bucket)
+    }
 
-        let platform_config = PlatformConfig {
-            admin: self.platform_admin.pubkey(),
-            fee_wallet: self.fee_wallet,
+    async fn process_payment_ix(&mut self, subscriber: &Subscriber, extras: ChargeExtras) -> Instruction {
+        let (schedule_bucket, next_schedule_bucket) = self.charge_buckets(subscriber).await;
+        let subscription = self.subscription(subscriber).await;
+        let platform_config: PlatformConfig = self.fetch(platform_config_pda()).await;
+        let recipient = subscriber.recipient;
+        let (settlement, settlement_vault, held_payment) = if extras.settlement {
+            (
+                Some(pda(&[b"settlement", recipient.as_ref()])),
+                Some(pda(&[b"settlement-vault", recipient.as_ref()])),
+                Some(pda(&[
+                    b"held",
+                    recipient.as_ref(),
+                    subscriber.subscription.as_ref(),
+                    &subscription.next_payment_due.to_le_bytes(),
+                ])),
+            )
+        } else {
+            (None, None, None)
         };
 
-        context.banks_client
-            .process_transaction(Transaction::new_signed_with_payer(
-                &[platform_config],
-                Some(&context.payer.pubkey()),
-                &[&self.platform_admin],
-                context.last_blockhash,
-            ))
-            .unwrap();
+        let mut instruction = ix(
+            accounts::ProcessPayment {
+                subscription: subscriber.subscription,
+                subscription_signer: subscriber.subscription,
+                user: subscriber.user.pubkey(),
+                escrow_token_account: subscription.escrow_token_account,
+                recipient_token_account: recipient,
+                fee_wallet_token_account: self.fee_wallet_token_account,
+                platform_config: platform_config_pda(),
+                schedule_bucket,
+                next_schedule_bucket,
+                price_oracle: platform_config.price_oracle,
+                keeper: self.payer(),
+                keeper_token_account: self.keeper_token_account,
+                usdc_mint: self.mint,
+                plan: extras.plan,
+                referrer: extras.referrer.map(|(referrer, _)| referrer),
+                referrer_token_account: extras.referrer.map(|(_, token_account)| token_account),
+                payout_split: payout_split_pda(&recipient),
+                settlement,
+                settlement_vault,
+                held_payment,
+                token_program: spl_token::ID,
+                system_program: solana_sdk::system_program::ID,
+            },
+            instruction::ProcessPayment {},
+        );
+        instruction.accounts.extend(
+            extras
+                .payees
+                .iter()
+                .map(|payee| AccountMeta::new(*payee, false)),
+        );
+        instruction
+    }
+
+    async fn process_payment(&mut self, subscriber: &Subscriber, extras: ChargeExtras) -> Result<(), BanksClientError> {
+        let instruction = self.process_payment_ix(subscriber, extras).await;
+        self.send(&[instruction], &[]).await
     }
 
-    fn create_associated_token_account(&self, context: &mut TestContext, owner: &Keypair, mint: &SolanaPubkey) -> SolanaPubkey {
-        let associated_token = AssociatedToken::get_associated_token_address(owner.pubkey(), mint);
-        // Create associated token accounts for the owner
-        let create_associated_token_ix = AssociatedToken::create_associated_token_account(
-            context.payer.pubkey(),
-            owner.pubkey(),
-            mint,
+    async fn cancel(&mut self, subscriber: &Subscriber, mode: CancelMode) -> Result<(), BanksClientError> {
+        let subscription = self.subscription(subscriber).await;
+        let schedule_bucket = schedule_bucket_pda(
+            ScheduleBucket::day_of(subscription.next_payment_due),
+            subscription.schedule_page,
         );
+        self.send(
+            &[ix(
+                accounts::CancelSubscription {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                    subscription_signer: subscriber.subscription,
+                    escrow_token_account: subscriber.escrow,
+                    destination_token_account: subscriber.wallet_token_account,
+                    recipient_token_account: subscriber.recipient,
+                    schedule_bucket,
+                    pending_refund: None,
+                    token_program: spl_token::ID,
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::CancelSubscription { mode },
+            )],
+            &[&subscriber.user],
+        )
+        .await
+    }
 
-        context.banks_client
-            .process_transaction(Transaction::new_signed_with_payer(
-                &[create_associated_token_ix],
-                Some(&context.payer.pubkey()),
-                &[context.payer, owner],
-                context.last_blockhash,
-            ))
-            .unwrap();
+    async fn create_plan(&mut self, merchant: &Merchant, plan_id: u64, price: u64, terms: PlanTerms) -> Pubkey {
+        let plan = pda(&[b"plan", merchant.wallet.pubkey().as_ref(), &plan_id.to_le_bytes()]);
+        self.send(
+            &[ix(
+                accounts::CreatePlan {
+                    plan,
+                    merchant: merchant.wallet.pubkey(),
+                    recipient_token_account: merchant.recipient,
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::CreatePlan {
+                    plan_id,
+                    price,
+                    interval_seconds: BILLING_INTERVAL_SECONDS,
+                    prorated: terms.prorated,
+                    yield_split: None,
+                    unit_price: terms.unit_price,
+                    setup_fee: terms.setup_fee,
+                },
+            )],
+            &[&merchant.wallet],
+        )
+        .await
+        .unwrap();
+        plan
+    }
+
+    async fn update_plan_price(&mut self, merchant: &Merchant, plan: Pubkey, new_price: u64) {
+        self.send(
+            &[ix(
+                accounts::UpdatePlanPrice { plan, merchant: merchant.wallet.pubkey() },
+                instruction::UpdatePlanPrice { new_price },
+            )],
+            &[&merchant.wallet],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Move the clock to the subscription's next due time and charge it
+    async fn accept_price_change(&mut self, subscriber: &Subscriber, plan: Pubkey) {
+        self.send(
+            &[ix(
+                accounts::AcceptPriceChange {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                    plan,
+                },
+                instruction::AcceptPriceChange {},
+            )],
+            &[&subscriber.user],
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn charge_next_period(&mut self, subscriber: &Subscriber, extras: ChargeExtras) -> Result<(), BanksClientError> {
+        let due = self.subscription(subscriber).await.next_payment_due;
+        self.warp_to(due).await;
+        self.process_payment(subscriber, extras).await
+    }
+
+    async fn set_spending_cap(&mut self, subscriber: &Subscriber, max_amount_per_period: u64, lifetime_cap: Option<u64>) {
+        self.send(
+            &[ix(
+                accounts::SetSpendingCap {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                },
+                instruction::SetSpendingCap { max_amount_per_period, lifetime_cap },
+            )],
+            &[&subscriber.user],
+        )
+        .await
+        .unwrap();
+    }
 
-        associated_token
+    async fn attach_add_on(
+        &mut self,
+        subscriber: &Subscriber,
+        merchant: &Merchant,
+        id: u64,
+        price: u64,
+        interval_seconds: i64,
+    ) -> Result<(), BanksClientError> {
+        self.send(
+            &[ix(
+                accounts::AttachAddOn {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                    recipient_token_account: merchant.recipient,
+                    merchant: merchant.wallet.pubkey(),
+                },
+                instruction::AttachAddOn { id, price, interval_seconds },
+            )],
+            &[&subscriber.user, &merchant.wallet],
+        )
+        .await
+    }
+
+    async fn create_coupon(&mut self, merchant: &Merchant, code: &[u8], discount: Discount, duration_periods: u32) -> Pubkey {
+        let mut padded = [0u8; 16];
+        padded[..code.len()].copy_from_slice(code);
+        let coupon = pda(&[b"coupon", merchant.wallet.pubkey().as_ref(), &padded]);
+        self.send(
+            &[ix(
+                accounts::CreateCoupon {
+                    coupon,
+                    merchant: merchant.wallet.pubkey(),
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::CreateCoupon {
+                    code: padded,
+                    discount,
+                    duration_periods,
+                    max_redemptions: 0,
+                    expires_at: None,
+                },
+            )],
+            &[&merchant.wallet],
+        )
+        .await
+        .unwrap();
+        coupon
+    }
+
+    async fn apply_coupon(&mut self, subscriber: &Subscriber, coupon: Pubkey) -> Result<(), BanksClientError> {
+        self.send(
+            &[ix(
+                accounts::ApplyCoupon {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                    coupon,
+                    coupon_redemption: coupon_redemption_pda(&coupon, &subscriber.subscription),
+                    recipient_token_account: subscriber.recipient,
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::ApplyCoupon {},
+            )],
+            &[&subscriber.user],
+        )
+        .await
+    }
+
+    async fn admin_update(&mut self, instruction: Instruction) {
+        self.send(&[instruction], &[]).await.unwrap();
+    }
+
+    async fn set_payout_split(&mut self, merchant: &Merchant, payees: Vec<Payee>) {
+        self.send(
+            &[ix(
+                accounts::SetPayoutSplit {
+                    payout_split: payout_split_pda(&merchant.recipient),
+                    recipient_token_account: merchant.recipient,
+                    merchant: merchant.wallet.pubkey(),
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::SetPayoutSplit { payees },
+            )],
+            &[&merchant.wallet],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Hold settlements for `settlement_delay` and open `merchant`'s settlement
+    async fn hold_settlements(&mut self, merchant: &Merchant, settlement_delay: i64) {
+        let admin = self.payer();
+        self.admin_update(ix(
+            accounts::UpdateSettlementDelay { platform_config: platform_config_pda(), admin },
+            instruction::UpdateSettlementDelay { settlement_delay },
+        ))
+        .await;
+        let recipient = merchant.recipient;
+        let (mint, payer) = (self.mint, self.payer());
+        self.send(
+            &[ix(
+                accounts::OpenSettlement {
+                    settlement: pda(&[b"settlement", recipient.as_ref()]),
+                    settlement_vault: pda(&[b"settlement-vault", recipient.as_ref()]),
+                    recipient_token_account: recipient,
+                    usdc_mint: mint,
+                    payer,
+                    token_program: spl_token::ID,
+                    system_program: solana_sdk::system_program::ID,
+                    rent: solana_sdk::sysvar::rent::ID,
+                },
+                instruction::OpenSettlement {},
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn claim_settlement(&mut self, merchant: &Merchant, payees: &[Pubkey]) -> Result<(), BanksClientError> {
+        let recipient = merchant.recipient;
+        let mut instruction = ix(
+            accounts::ClaimSettlement {
+                settlement: pda(&[b"settlement", recipient.as_ref()]),
+                settlement_vault: pda(&[b"settlement-vault", recipient.as_ref()]),
+                recipient_token_account: recipient,
+                merchant: merchant.wallet.pubkey(),
+                token_program: spl_token::ID,
+            },
+            instruction::ClaimSettlement {},
+        );
+        instruction
+            .accounts
+            .extend(payees.iter().map(|payee| AccountMeta::new(*payee, false)));
+        self.send(&[instruction], &[&merchant.wallet]).await
+    }
+
+    /// One process_payments_batch entry: subscription, escrow, recipient, plan, payout split
+    fn batch_entry(subscriber: &Subscriber, plan: Option<Pubkey>) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(subscriber.subscription, false),
+            AccountMeta::new(subscriber.escrow, false),
+            AccountMeta::new(subscriber.recipient, false),
+            AccountMeta::new_readonly(plan.unwrap_or(program_id()), false),
+            AccountMeta::new_readonly(payout_split_pda(&subscriber.recipient), false),
+        ]
+    }
+
+    async fn process_payments_batch(&mut self, due_bucket: Pubkey, next_bucket: Pubkey, entries: Vec<AccountMeta>) -> Result<(), BanksClientError> {
+        let platform_config: PlatformConfig = self.fetch(platform_config_pda()).await;
+        let mut instruction = ix(
+            accounts::ProcessPaymentsBatch {
+                fee_wallet_token_account: self.fee_wallet_token_account,
+                platform_config: platform_config_pda(),
+                due_bucket,
+                next_bucket,
+                price_oracle: platform_config.price_oracle,
+                keeper: self.payer(),
+                keeper_token_account: self.keeper_token_account,
+                usdc_mint: self.mint,
+                token_program: spl_token::ID,
+            },
+            instruction::ProcessPaymentsBatch {},
+        );
+        instruction.accounts.extend(entries);
+        self.send(&[instruction], &[]).await
     }
 }
 
 #[tokio::test]
 async fn test_initialize_subscription() {
-    let test_ctx = TestContext::new();
-    test_ctx.create_accounts();
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
 
-    let mut context = test_ctx.program_test.start_with_context().unwrap();
+    let now = test.now().await;
+    let subscription = test.subscription(&subscriber).await;
+    assert!(subscription.is_active);
+    assert_eq!(subscription.user, subscriber.user.pubkey());
+    assert_eq!(subscription.escrow_token_account, subscriber.escrow);
+    assert_eq!(subscription.recipient_token_account, merchant.recipient);
+    assert_eq!(subscription.monthly_amount, MONTHLY_AMOUNT);
+    assert_eq!(subscription.next_payment_due, now);
 
-    let subscription = Subscription {
-        user: test_ctx.user.pubkey(),
-        monthly_amount: 10 * 1_000_000,  // 10 USDC, assuming 6 decimals
-        is_active: true,
-        bump: 0,
-    };
+    // The first charge is filed under today's schedule page
+    let bucket: ScheduleBucket = test.fetch(schedule_bucket_pda(ScheduleBucket::day_of(now), 0)).await;
+    assert_eq!(bucket.subscriptions, vec![subscriber.subscription]);
+}
 
-    let subscription_pda = test_ctx.subscription_pda;
-    let fee_wallet = test_ctx.fee_wallet;
+#[tokio::test]
+async fn test_process_payment_reimburses_keeper_gas() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let due = test.subscription(&subscriber).await.next_payment_due;
 
-    let transaction = Transaction::new_signed_with_payer(
-        &[
-            init_subscription(
-                &test_ctx.program_test,
-                test_ctx.user.pubkey(),
-                subscription.monthly_amount,
-                fee_wallet,
-            ),
-        ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &test_ctx.user],
-        context.last_blockhash,
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
+
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
+    assert_eq!(test.balance(test.fee_wallet_token_account).await, FEE - KEEPER_REWARD);
+    assert_eq!(test.balance(test.keeper_token_account).await, KEEPER_REWARD + GAS);
+    assert_eq!(
+        test.balance(subscriber.escrow).await,
+        2 * MONTHLY_AMOUNT - FEE - GAS
     );
 
-    context.banks_client.process_transaction(transaction).unwrap();
-    let subscription_data = context.banks_client
-        .get_account_data(&subscription_pda)
-        .unwrap();
+    // The subscription moves to the next period's schedule page
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.next_payment_due, due + subscription.interval_seconds);
+    assert_eq!(subscription.lifetime_charged, MONTHLY_AMOUNT + FEE + GAS);
+    let bucket: ScheduleBucket = test.fetch(schedule_bucket_pda(ScheduleBucket::day_of(due), 0)).await;
+    assert!(bucket.subscriptions.is_empty());
+    let next_bucket: ScheduleBucket = test
+        .fetch(schedule_bucket_pda(ScheduleBucket::day_of(subscription.next_payment_due), 0))
+        .await;
+    assert_eq!(next_bucket.subscriptions, vec![subscriber.subscription]);
+}
 
-    assert_eq!(subscription_data.user, test_ctx.user.pubkey());
-    assert_eq!(subscription_data.monthly_amount, subscription.monthly_amount);
+#[tokio::test]
+async fn test_process_payment_rejects_duplicate_crank() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::PaymentNotDue);
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
 }
 
 #[tokio::test]
-async fn test_process_payment() {
-    let test_ctx = TestContext::new();
-    test_ctx.create_accounts();
+async fn test_process_payment_rejects_underfunded_escrow() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, MONTHLY_AMOUNT, Terms::default()).await;
 
-    let mut context = test_ctx.program_test.start_with_context().unwrap();
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::InsufficientFunds);
 
-    let payment_amount = 10 * 1_000_000;  // 10 USDC, assuming 6 decimals
-    let user_token_account = test_ctx.create_associated_token_account(&mut context, &test_ctx.user, &test_ctx.mint.pubkey());
-    let escrow_token_account = test_ctx.create_associated_token_account(&mut context, &test_ctx.user, &test_ctx.mint.pubkey());
-    let recipient_token_account = test_ctx.create_associated_token_account(&mut context, &test_ctx.platform_admin, &test_ctx.mint.pubkey());
+    // Enough for the charge and fee, but not the keeper's gas
+    test.token_account(&subscriber.subscription, MONTHLY_AMOUNT + FEE);
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::InsufficientGasFeeFunds);
+}
 
-    // Add payment to escrow
-    let escrow_token_account_balance = escrow_token_account.amount;
-    assert_eq!(escrow_token_account_balance, payment_amount);
+#[tokio::test]
+async fn test_cancel_subscription_refunds_escrow() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
 
-    let transaction = Transaction::new_signed_with_payer(
-        &[
-            process_payment(
-                &test_ctx.program_test,
-                test_ctx.user.pubkey(),
-                payment_amount,
-                user_token_account,
-                escrow_token_account,
-                recipient_token_account,
-            ),
-        ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &test_ctx.user],
-        context.last_blockhash,
+    // Only the subscription's own escrow can be refunded from
+    let subscription = test.subscription(&subscriber).await;
+    let schedule_bucket = schedule_bucket_pda(
+        ScheduleBucket::day_of(subscription.next_payment_due),
+        subscription.schedule_page,
     );
+    let result = test
+        .send(
+            &[ix(
+                accounts::CancelSubscription {
+                    subscription: subscriber.subscription,
+                    user: subscriber.user.pubkey(),
+                    subscription_signer: subscriber.subscription,
+                    escrow_token_account: subscriber.wallet_token_account,
+                    destination_token_account: subscriber.wallet_token_account,
+                    recipient_token_account: subscriber.recipient,
+                    schedule_bucket,
+                    pending_refund: None,
+                    token_program: spl_token::ID,
+                    system_program: solana_sdk::system_program::ID,
+                },
+                instruction::CancelSubscription { mode: CancelMode::Immediate },
+            )],
+            &[&subscriber.user],
+        )
+        .await;
+    assert_error(result, SubscriptionError::EscrowAccountNotFound);
 
-    context.banks_client.process_transaction(transaction).unwrap();
-    let updated_balance = context.banks_client
-        .get_account_data(&recipient_token_account)
-        .unwrap()
-        .amount;
+    test.cancel(&subscriber, CancelMode::Immediate).await.unwrap();
 
-    assert_eq!(updated_balance, payment_amount);
+    assert_eq!(test.balance(subscriber.wallet_token_account).await, 3 * MONTHLY_AMOUNT);
+    assert_eq!(test.balance(subscriber.escrow).await, 0);
+    assert!(!test.subscription(&subscriber).await.is_active);
+
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::SubscriptionInactive);
 }
 
 #[tokio::test]
-async fn test_cancel_subscription() {
-    let test_ctx = TestContext::new();
-    test_ctx.create_accounts();
+async fn test_cancel_at_period_end_stops_charges() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
 
-    let mut context = test_ctx.program_test.start_with_context().unwrap();
+    test.cancel(&subscriber, CancelMode::AtPeriodEnd).await.unwrap();
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.cancel_at, Some(subscription.next_payment_due));
 
-    let transaction = Transaction::new_signed_with_payer(
-        &[
-            cancel_subscription(
-                &test_ctx.program_test,
-                test_ctx.user.pubkey(),
-                test_ctx.subscription_pda,
-            ),
-        ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &test_ctx.user],
-        context.last_blockhash,
+    test.warp_to(subscription.next_payment_due).await;
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::SubscriptionEnding);
+
+    // Anyone may finalize once the paid period is over
+    let escrow_balance = test.balance(subscriber.escrow).await;
+    let schedule_bucket = schedule_bucket_pda(ScheduleBucket::day_of(subscription.next_payment_due), 0);
+    let payer = test.payer();
+    test.send(
+        &[ix(
+            accounts::FinalizeCancellation {
+                subscription: subscriber.subscription,
+                subscription_signer: subscriber.subscription,
+                escrow_token_account: subscriber.escrow,
+                destination_token_account: subscriber.wallet_token_account,
+                recipient_token_account: merchant.recipient,
+                schedule_bucket,
+                pending_refund: None,
+                payer,
+                token_program: spl_token::ID,
+                system_program: solana_sdk::system_program::ID,
+            },
+            instruction::FinalizeCancellation {},
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(test.balance(subscriber.wallet_token_account).await, escrow_balance);
+    assert!(!test.subscription(&subscriber).await.is_active);
+}
+
+#[tokio::test]
+async fn test_close_empty_past_schedule_bucket_refunds_payer() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let today = test.now().await;
+    let bucket = schedule_bucket_pda(ScheduleBucket::day_of(today), 0);
+    let payer = test.payer();
+    let close = ix(
+        accounts::CloseScheduleBucket { schedule_bucket: bucket, payer },
+        instruction::CloseScheduleBucket {},
     );
 
-    context.banks_client.process_transaction(transaction).unwrap();
-    let updated_subscription = context.banks_client
-        .get_account_data(&test_ctx.subscription_pda)
+    // Still holds the subscription's first charge
+    let result = test.send(std::slice::from_ref(&close), &[]).await;
+    assert_error(result, SubscriptionError::ScheduleBucketInUse);
+
+    // Emptied, but the day hasn't passed yet
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
+    let result = test.send(std::slice::from_ref(&close), &[]).await;
+    assert_error(result, SubscriptionError::ScheduleBucketInUse);
+
+    test.warp_to(today + SECONDS_PER_DAY).await;
+    let rent = test.lamports(bucket).await;
+    let before = test.lamports(payer).await;
+    test.send(&[close], &[]).await.unwrap();
+    assert!(!test.exists(bucket).await);
+    // Refunded the page's rent, less this transaction's fee
+    assert_eq!(test.lamports(payer).await, before + rent - 5_000);
+}
+
+#[tokio::test]
+async fn test_process_payments_batch_skips_underfunded() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let funded = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let underfunded = test.subscribe(&merchant, MONTHLY_AMOUNT, MONTHLY_AMOUNT, Terms::default()).await;
+    let (due_bucket, next_bucket) = test.charge_buckets(&funded).await;
+
+    let mut entries = Test::batch_entry(&funded, None);
+    entries.extend(Test::batch_entry(&underfunded, None));
+    test.process_payments_batch(due_bucket, next_bucket, entries).await.unwrap();
+
+    // The one entry charged repays the batch's whole gas cost
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
+    assert_eq!(test.balance(test.keeper_token_account).await, KEEPER_REWARD + GAS);
+    assert_eq!(test.balance(funded.escrow).await, 2 * MONTHLY_AMOUNT - FEE - GAS);
+    assert_eq!(test.balance(underfunded.escrow).await, MONTHLY_AMOUNT);
+
+    // The skipped entry stays filed under the day it's due
+    let bucket: ScheduleBucket = test.fetch(due_bucket).await;
+    assert_eq!(bucket.subscriptions, vec![underfunded.subscription]);
+    let bucket: ScheduleBucket = test.fetch(next_bucket).await;
+    assert_eq!(bucket.subscriptions, vec![funded.subscription]);
+}
+
+#[tokio::test]
+async fn test_process_payments_batch_splits_gas_between_charged_entries() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let first = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let second = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let (due_bucket, next_bucket) = test.charge_buckets(&first).await;
+
+    let mut entries = Test::batch_entry(&first, None);
+    entries.extend(Test::batch_entry(&second, None));
+    test.process_payments_batch(due_bucket, next_bucket, entries).await.unwrap();
+
+    assert_eq!(test.balance(merchant.recipient).await, 2 * MONTHLY_AMOUNT);
+    assert_eq!(test.balance(test.keeper_token_account).await, 2 * KEEPER_REWARD + GAS);
+    assert_eq!(test.balance(first.escrow).await, 2 * MONTHLY_AMOUNT - FEE - GAS / 2);
+    assert_eq!(test.balance(second.escrow).await, 2 * MONTHLY_AMOUNT - FEE - GAS / 2);
+}
+
+#[tokio::test]
+async fn test_process_payments_batch_rejects_repeated_entries() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let (due_bucket, next_bucket) = test.charge_buckets(&subscriber).await;
+
+    let mut entries = Test::batch_entry(&subscriber, None);
+    entries.extend(Test::batch_entry(&subscriber, None));
+    let result = test.process_payments_batch(due_bucket, next_bucket, entries).await;
+    assert_error(result, SubscriptionError::InvalidBatch);
+    assert_eq!(test.balance(subscriber.escrow).await, 3 * MONTHLY_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_change_plan_defers_downgrade_to_next_period() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let premium = test.create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms::default()).await;
+    let basic = test.create_plan(&merchant, 2, MONTHLY_AMOUNT / 2, PlanTerms::default()).await;
+    let terms = Terms { plan: Some(premium), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    let extras = || ChargeExtras { plan: Some(premium), ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras()).await.unwrap();
+
+    // An accepted increase on the old plan doesn't carry over to the new one
+    test.update_plan_price(&merchant, premium, 2 * MONTHLY_AMOUNT).await;
+    test.accept_price_change(&subscriber, premium).await;
+    test.send(
+        &[ix(
+            accounts::ChangePlan {
+                subscription: subscriber.subscription,
+                user: subscriber.user.pubkey(),
+                subscription_signer: subscriber.subscription,
+                current_plan: premium,
+                new_plan: basic,
+                escrow_token_account: subscriber.escrow,
+                recipient_token_account: merchant.recipient,
+                token_program: spl_token::ID,
+            },
+            instruction::ChangePlan { defer_downgrade: true },
+        )],
+        &[&subscriber.user],
+    )
+    .await
+    .unwrap();
+
+    // The paid period keeps the old plan; the next charge is at the new price
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.plan, Some(premium));
+    assert_eq!(subscription.pending_plan, Some(basic));
+    assert!(subscription.accepted_price.is_none());
+
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT + MONTHLY_AMOUNT / 2);
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.plan, Some(basic));
+    assert_eq!(subscription.pending_plan, None);
+    assert_eq!(subscription.monthly_amount, MONTHLY_AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn test_unaccepted_price_increase_ends_subscription() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let plan = test.create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms::default()).await;
+    let terms = Terms { plan: Some(plan), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    let extras = || ChargeExtras { plan: Some(plan), ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras()).await.unwrap();
+
+    // The notice runs out exactly as the next period starts
+    test.update_plan_price(&merchant, plan, 2 * MONTHLY_AMOUNT).await;
+    let due = test.subscription(&subscriber).await.next_payment_due;
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+
+    // Not charged the new price; ends with the period already paid for
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.cancel_at, Some(due));
+    assert_eq!(subscription.monthly_amount, MONTHLY_AMOUNT);
+    assert_eq!(subscription.refund_destination, Some(subscriber.wallet_token_account));
+}
+
+#[tokio::test]
+async fn test_accepted_price_increase_applies() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let plan = test.create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms::default()).await;
+    let terms = Terms { plan: Some(plan), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    let extras = || ChargeExtras { plan: Some(plan), ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras()).await.unwrap();
+
+    test.update_plan_price(&merchant, plan, 2 * MONTHLY_AMOUNT).await;
+    test.accept_price_change(&subscriber, plan).await;
+
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, 3 * MONTHLY_AMOUNT);
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.monthly_amount, 2 * MONTHLY_AMOUNT);
+    assert!(subscription.accepted_price.is_none());
+}
+
+#[tokio::test]
+async fn test_metered_plan_bills_reported_usage() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let unit_price = 1_000;
+    let plan = test
+        .create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms { unit_price, ..PlanTerms::default() })
+        .await;
+    let terms = Terms { plan: Some(plan), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    let extras = || ChargeExtras { plan: Some(plan), ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras()).await.unwrap();
+
+    for (quantity, record_id) in [(30, 1), (12, 2)] {
+        test.send(
+            &[ix(
+                accounts::ReportUsage {
+                    subscription: subscriber.subscription,
+                    plan,
+                    merchant: merchant.wallet.pubkey(),
+                },
+                instruction::ReportUsage { quantity, record_id },
+            )],
+            &[&merchant.wallet],
+        )
+        .await
         .unwrap();
+    }
+    assert_eq!(test.subscription(&subscriber).await.usage_units, 42);
 
-    assert!(!updated_subscription.is_active);
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, 2 * MONTHLY_AMOUNT + 42 * unit_price);
+    assert_eq!(test.subscription(&subscriber).await.usage_units, 0);
 }
 
 #[tokio::test]
-async fn test_update_fee_wallet() {
-    let test_ctx = TestContext::new();
-    test_ctx.create_accounts();
+async fn test_setup_fee_and_add_ons_billed_with_charge() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let setup_fee = 5_000_000;
+    let plan = test
+        .create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms { setup_fee, ..PlanTerms::default() })
+        .await;
+    let terms = Terms { plan: Some(plan), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    assert_eq!(test.balance(merchant.recipient).await, setup_fee);
+
+    // A weekly add-on has several periods due by the next monthly charge
+    let add_on_price = 1_000_000;
+    let week = 7 * SECONDS_PER_DAY;
+    test.attach_add_on(&subscriber, &merchant, 7, add_on_price, week).await.unwrap();
+
+    let extras = || ChargeExtras { plan: Some(plan), ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, setup_fee + MONTHLY_AMOUNT + add_on_price);
+
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+    let periods = (BILLING_INTERVAL_SECONDS / week + 1) as u64;
+    assert_eq!(
+        test.balance(merchant.recipient).await,
+        setup_fee + 2 * MONTHLY_AMOUNT + (1 + periods - 1) * add_on_price
+    );
+}
+
+#[tokio::test]
+async fn test_coupon_discounts_limited_periods() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let coupon = test.create_coupon(&merchant, b"HALF", Discount::PercentOff { bps: 5_000 }, 2).await;
+    let terms = Terms { coupon: Some(coupon), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
+    test.charge_next_period(&subscriber, ChargeExtras::default()).await.unwrap();
+    test.charge_next_period(&subscriber, ChargeExtras::default()).await.unwrap();
+
+    // Half price twice, then full price
+    assert_eq!(test.balance(merchant.recipient).await, 2 * MONTHLY_AMOUNT);
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.coupon_periods_left, 0);
+    assert!(subscription.coupon_discount.is_none());
+}
+
+#[tokio::test]
+async fn test_coupon_cannot_be_reapplied_after_it_runs_out() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let coupon = test.create_coupon(&merchant, b"ONCE", Discount::AmountOff { amount: 1_000_000 }, 1).await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, Terms::default()).await;
+
+    test.apply_coupon(&subscriber, coupon).await.unwrap();
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT - 1_000_000);
+
+    let result = test.apply_coupon(&subscriber, coupon).await;
+    assert_error(result, SubscriptionError::CouponAlreadyRedeemed);
+
+    // Nor once another coupon has replaced it
+    let other = test.create_coupon(&merchant, b"OTHER", Discount::AmountOff { amount: 1_000_000 }, 1).await;
+    test.apply_coupon(&subscriber, other).await.unwrap();
+    test.charge_next_period(&subscriber, ChargeExtras::default()).await.unwrap();
+    assert_eq!(test.subscription(&subscriber).await.coupon, Some(other));
+    let result = test.apply_coupon(&subscriber, coupon).await;
+    assert_error(result, SubscriptionError::CouponAlreadyRedeemed);
+}
+
+#[tokio::test]
+async fn test_referrer_earns_share_for_limited_periods() {
+    let mut test = Test::new().await;
+    let admin = test.payer();
+    test.send(
+        &[ix(
+            accounts::UpdateReferralProgram { platform_config: platform_config_pda(), admin },
+            instruction::UpdateReferralProgram { referral_share_bps: 2_500, referral_periods: 1 },
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let wallet = Keypair::new();
+    test.fund(&wallet.pubkey()).await;
+    let referrer = pda(&[b"referrer", wallet.pubkey().as_ref()]);
+    test.send(
+        &[ix(
+            accounts::RegisterReferrer {
+                referrer,
+                wallet: wallet.pubkey(),
+                system_program: solana_sdk::system_program::ID,
+            },
+            instruction::RegisterReferrer {},
+        )],
+        &[&wallet],
+    )
+    .await
+    .unwrap();
+    let referrer_token_account = test.token_account(&wallet.pubkey(), 0);
+
+    let merchant = test.merchant().await;
+    let terms = Terms { referrer: Some(referrer), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    let extras = || ChargeExtras {
+        referrer: Some((referrer, referrer_token_account)),
+        ..ChargeExtras::default()
+    };
+
+    // A quarter of the platform's part of the fee, for the first period only
+    let referral_fee = (FEE - KEEPER_REWARD) / 4;
+    test.process_payment(&subscriber, extras()).await.unwrap();
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+    assert_eq!(test.balance(referrer_token_account).await, referral_fee);
+    assert_eq!(
+        test.balance(test.fee_wallet_token_account).await,
+        2 * (FEE - KEEPER_REWARD) - referral_fee
+    );
+
+    let referrer: subscription_manager::state::Referrer = test.fetch(referrer).await;
+    assert_eq!(referrer.referred_subscriptions, 1);
+    assert_eq!(referrer.periods_paid, 1);
+    assert_eq!(referrer.total_earned, referral_fee);
+}
+
+#[tokio::test]
+async fn test_spending_cap_blocks_charge_over_limit() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, Terms::default()).await;
+
+    // The cap covers fees too, so the bare price isn't enough
+    test.set_spending_cap(&subscriber, MONTHLY_AMOUNT, None).await;
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::SpendingCapExceeded);
 
-    let mut context = test_ctx.program_test.start_with_context().unwrap();
+    test.set_spending_cap(&subscriber, MONTHLY_AMOUNT + FEE + GAS, Some(MONTHLY_AMOUNT + FEE + GAS)).await;
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
 
-    let new_fee_wallet = Keypair::new().pubkey();
+    // The lifetime cap is used up by the first charge
+    let result = test.charge_next_period(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::SpendingCapExceeded);
+}
+
+#[tokio::test]
+async fn test_spending_cap_blocks_add_on_over_limit() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, Terms::default()).await;
+    let add_on_price = 1_000_000;
+    test.set_spending_cap(&subscriber, MONTHLY_AMOUNT + FEE + add_on_price, None).await;
+
+    // The first add-on fits the cap; a second would push the next charge over it
+    test.attach_add_on(&subscriber, &merchant, 1, add_on_price, BILLING_INTERVAL_SECONDS)
+        .await
+        .unwrap();
+    let result = test
+        .attach_add_on(&subscriber, &merchant, 2, add_on_price, BILLING_INTERVAL_SECONDS)
+        .await;
+    assert_error(result, SubscriptionError::SpendingCapExceeded);
+}
+
+#[tokio::test]
+async fn test_delegated_subscription_goes_past_due_without_allowance() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let user = Keypair::new();
+    test.fund(&user.pubkey()).await;
+    let subscriber = Subscriber {
+        wallet_token_account: test.token_account(&user.pubkey(), 5 * MONTHLY_AMOUNT),
+        escrow: get_associated_token_address(&user.pubkey(), &test.mint),
+        subscription: subscription_pda(&user.pubkey()),
+        recipient: merchant.recipient,
+        user,
+    };
+    let now = test.now().await;
+    let schedule_bucket = test.schedule_bucket(now).await;
+    let fee_wallet = test.fee_wallet.pubkey();
+    test.send(
+        &[ix(
+            accounts::InitializeDelegatedSubscription {
+                subscription: subscriber.subscription,
+                user: subscriber.user.pubkey(),
+                fee_wallet,
+                user_token_account: subscriber.wallet_token_account,
+                recipient_token_account: merchant.recipient,
+                schedule_bucket,
+                plan: None,
+                token_program: spl_token::ID,
+                system_program: solana_sdk::system_program::ID,
+            },
+            instruction::InitializeDelegatedSubscription {
+                monthly_amount: MONTHLY_AMOUNT,
+                fee_wallet,
+                allowance: MONTHLY_AMOUNT + FEE + GAS,
+            },
+        )],
+        &[&subscriber.user],
+    )
+    .await
+    .unwrap();
+
+    // Pulled straight from the user's wallet
+    test.process_payment(&subscriber, ChargeExtras::default()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
+    assert_eq!(test.balance(subscriber.wallet_token_account).await, 4 * MONTHLY_AMOUNT - FEE - GAS);
+
+    // The allowance is spent: the period stays unpaid and is retried later
+    test.charge_next_period(&subscriber, ChargeExtras::default()).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
+    let subscription = test.subscription(&subscriber).await;
+    assert!(subscription.past_due);
+    assert!(subscription.is_active);
+}
+
+#[tokio::test]
+async fn test_prorated_cancel_refunds_unused_period() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let plan = test
+        .create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms { prorated: true, ..PlanTerms::default() })
+        .await;
+    let terms = Terms { plan: Some(plan), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, terms).await;
+    test.process_payment(&subscriber, ChargeExtras { plan: Some(plan), ..ChargeExtras::default() })
+        .await
+        .unwrap();
+
+    // The period's payment is held in escrow rather than paid out up front
+    assert_eq!(test.balance(merchant.recipient).await, 0);
+    let subscription = test.subscription(&subscriber).await;
+    assert_eq!(subscription.held_amount, MONTHLY_AMOUNT);
 
-    let transaction = Transaction::new_signed_with_payer(
-        &[
-            update_fee_wallet(
-                &test_ctx.program_test,
-                test_ctx.platform_admin.pubkey(),
-                new_fee_wallet,
-            ),
+    test.warp_to(subscription.next_payment_due - BILLING_INTERVAL_SECONDS / 2).await;
+    test.cancel(&subscriber, CancelMode::Immediate).await.unwrap();
+
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT / 2);
+    assert_eq!(
+        test.balance(subscriber.wallet_token_account).await,
+        3 * MONTHLY_AMOUNT - FEE - GAS - MONTHLY_AMOUNT / 2
+    );
+}
+
+#[tokio::test]
+async fn test_payout_split_divides_charge() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let partner = Keypair::new();
+    let partner_token_account = test.token_account(&partner.pubkey(), 0);
+    test.set_payout_split(
+        &merchant,
+        vec![
+            Payee { token_account: merchant.recipient, bps: 7_000 },
+            Payee { token_account: partner_token_account, bps: 3_000 },
+        ],
+    )
+    .await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+
+    let extras = ChargeExtras {
+        payees: vec![merchant.recipient, partner_token_account],
+        ..ChargeExtras::default()
+    };
+    test.process_payment(&subscriber, extras).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT * 7 / 10);
+    assert_eq!(test.balance(partner_token_account).await, MONTHLY_AMOUNT * 3 / 10);
+
+    // Payees must be passed in the split's order
+    let extras = ChargeExtras {
+        payees: vec![partner_token_account, merchant.recipient],
+        ..ChargeExtras::default()
+    };
+    let result = test.charge_next_period(&subscriber, extras).await;
+    assert_error(result, SubscriptionError::PayeeMismatch);
+}
+
+#[tokio::test]
+async fn test_payout_split_routes_missing_payee_to_merchant() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    // Never created, so it can't receive its share
+    let missing = Pubkey::new_unique();
+    test.set_payout_split(
+        &merchant,
+        vec![
+            Payee { token_account: merchant.recipient, bps: 5_000 },
+            Payee { token_account: missing, bps: 5_000 },
         ],
-        Some(&context.payer.pubkey()),
-        &[&context.payer, &test_ctx.platform_admin],
-        context.last_blockhash,
+    )
+    .await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+
+    let extras = ChargeExtras { payees: vec![merchant.recipient, missing], ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras).await.unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, MONTHLY_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_settlement_held_until_released() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let delay = 7 * SECONDS_PER_DAY;
+    test.hold_settlements(&merchant, delay).await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let due = test.subscription(&subscriber).await.next_payment_due;
+    let extras = || ChargeExtras { settlement: true, ..ChargeExtras::default() };
+
+    // Without the settlement accounts the charge can't be taken
+    let result = test.process_payment(&subscriber, ChargeExtras::default()).await;
+    assert_error(result, SubscriptionError::SettlementRequired);
+
+    test.process_payment(&subscriber, extras()).await.unwrap();
+    let recipient = merchant.recipient;
+    let settlement_pda = pda(&[b"settlement", recipient.as_ref()]);
+    let vault = pda(&[b"settlement-vault", recipient.as_ref()]);
+    let held_payment = pda(&[b"held", recipient.as_ref(), subscriber.subscription.as_ref(), &due.to_le_bytes()]);
+    assert_eq!(test.balance(vault).await, MONTHLY_AMOUNT);
+    assert_eq!(test.balance(recipient).await, 0);
+    let settlement: Settlement = test.fetch(settlement_pda).await;
+    assert_eq!((settlement.held, settlement.released), (MONTHLY_AMOUNT, 0));
+
+    let payer = test.payer();
+    let release = ix(
+        accounts::ReleaseHeldPayment { settlement: settlement_pda, held_payment, payer },
+        instruction::ReleaseHeldPayment {},
     );
+    let result = test.send(std::slice::from_ref(&release), &[]).await;
+    assert_error(result, SubscriptionError::PaymentStillHeld);
+
+    let held: HeldPayment = test.fetch(held_payment).await;
+    test.warp_to(held.release_at).await;
+    test.send(&[release], &[]).await.unwrap();
+    assert!(!test.exists(held_payment).await);
+
+    test.claim_settlement(&merchant, &[recipient]).await.unwrap();
+    assert_eq!(test.balance(recipient).await, MONTHLY_AMOUNT);
+    let settlement: Settlement = test.fetch(settlement_pda).await;
+    assert_eq!((settlement.held, settlement.released), (0, 0));
+}
+
+#[tokio::test]
+async fn test_held_payment_keeps_split_from_charge_time() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    test.hold_settlements(&merchant, 7 * SECONDS_PER_DAY).await;
+    let partner = Keypair::new();
+    let partner_token_account = test.token_account(&partner.pubkey(), 0);
+    let recipient = merchant.recipient;
+    test.set_payout_split(
+        &merchant,
+        vec![
+            Payee { token_account: recipient, bps: 7_000 },
+            Payee { token_account: partner_token_account, bps: 3_000 },
+        ],
+    )
+    .await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let due = test.subscription(&subscriber).await.next_payment_due;
+    test.process_payment(&subscriber, ChargeExtras { settlement: true, ..ChargeExtras::default() })
+        .await
+        .unwrap();
 
-    context.banks_client.process_transaction(transaction).unwrap();
-    let updated_platform_config = context.banks_client
-        .get_account_data(&test_ctx.fee_wallet)
+    // Changing the split while the payment is held doesn't change who it is owed to
+    test.set_payout_split(&merchant, vec![Payee { token_account: recipient, bps: 10_000 }]).await;
+    let settlement_pda = pda(&[b"settlement", recipient.as_ref()]);
+    let held_payment = pda(&[b"held", recipient.as_ref(), subscriber.subscription.as_ref(), &due.to_le_bytes()]);
+    let held: HeldPayment = test.fetch(held_payment).await;
+    test.warp_to(held.release_at).await;
+    let payer = test.payer();
+    test.send(
+        &[ix(
+            accounts::ReleaseHeldPayment { settlement: settlement_pda, held_payment, payer },
+            instruction::ReleaseHeldPayment {},
+        )],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let result = test.claim_settlement(&merchant, &[recipient]).await;
+    assert_error(result, SubscriptionError::PayeeMismatch);
+    test.claim_settlement(&merchant, &[recipient, partner_token_account]).await.unwrap();
+    assert_eq!(test.balance(recipient).await, MONTHLY_AMOUNT * 7 / 10);
+    assert_eq!(test.balance(partner_token_account).await, MONTHLY_AMOUNT * 3 / 10);
+}
+
+#[tokio::test]
+async fn test_declined_price_change_holds_nothing() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    test.hold_settlements(&merchant, 7 * SECONDS_PER_DAY).await;
+    let plan = test.create_plan(&merchant, 1, MONTHLY_AMOUNT, PlanTerms::default()).await;
+    let terms = Terms { plan: Some(plan), ..Terms::default() };
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 5 * MONTHLY_AMOUNT, terms).await;
+    let extras = || ChargeExtras { plan: Some(plan), settlement: true, ..ChargeExtras::default() };
+    test.process_payment(&subscriber, extras()).await.unwrap();
+
+    // The crank ends the subscription without charging, so no held payment is opened
+    test.update_plan_price(&merchant, plan, 2 * MONTHLY_AMOUNT).await;
+    let due = test.subscription(&subscriber).await.next_payment_due;
+    test.charge_next_period(&subscriber, extras()).await.unwrap();
+    assert_eq!(test.subscription(&subscriber).await.cancel_at, Some(due));
+    let recipient = merchant.recipient;
+    let held_payment = pda(&[b"held", recipient.as_ref(), subscriber.subscription.as_ref(), &due.to_le_bytes()]);
+    assert!(!test.exists(held_payment).await);
+    let settlement: Settlement = test.fetch(pda(&[b"settlement", recipient.as_ref()])).await;
+    assert_eq!(settlement.held, MONTHLY_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_arbiter_partially_refunds_dispute() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    test.hold_settlements(&merchant, 7 * SECONDS_PER_DAY).await;
+    let subscriber = test.subscribe(&merchant, MONTHLY_AMOUNT, 3 * MONTHLY_AMOUNT, Terms::default()).await;
+    let due = test.subscription(&subscriber).await.next_payment_due;
+    test.process_payment(&subscriber, ChargeExtras { settlement: true, ..ChargeExtras::default() })
+        .await
         .unwrap();
 
-    assert_eq!(updated_platform_config.fee_wallet, new_fee_wallet);
+    let recipient = merchant.recipient;
+    let settlement_pda = pda(&[b"settlement", recipient.as_ref()]);
+    let held_payment = pda(&[b"held", recipient.as_ref(), subscriber.subscription.as_ref(), &due.to_le_bytes()]);
+    let dispute = pda(&[b"dispute", subscriber.subscription.as_ref(), &due.to_le_bytes()]);
+    let open_dispute = ix(
+        accounts::OpenDispute {
+            subscription: subscriber.subscription,
+            user: subscriber.user.pubkey(),
+            held_payment,
+            dispute,
+            platform_config: platform_config_pda(),
+            system_program: solana_sdk::system_program::ID,
+        },
+        instruction::OpenDispute { due_at: due, evidence_uri: "ar://receipt".to_string() },
+    );
+
+    // Nobody could resolve it yet
+    let result = test.send(std::slice::from_ref(&open_dispute), &[&subscriber.user]).await;
+    assert_error(result, SubscriptionError::ArbiterRequired);
+
+    let arbiter = Keypair::new();
+    let admin = test.payer();
+    test.admin_update(ix(
+        accounts::AppointArbiter { platform_config: platform_config_pda(), admin },
+        instruction::AppointArbiter { arbiter: arbiter.pubkey() },
+    ))
+    .await;
+    test.send(&[open_dispute], &[&subscriber.user]).await.unwrap();
+
+    // Frozen past its release time
+    let held: HeldPayment = test.fetch(held_payment).await;
+    test.warp_to(held.release_at).await;
+    let payer = test.payer();
+    let result = test
+        .send(
+            &[ix(
+                accounts::ReleaseHeldPayment { settlement: settlement_pda, held_payment, payer },
+                instruction::ReleaseHeldPayment {},
+            )],
+            &[],
+        )
+        .await;
+    assert_error(result, SubscriptionError::PaymentDisputed);
+
+    let refund_amount = MONTHLY_AMOUNT / 4;
+    test.send(
+        &[ix(
+            accounts::ResolveDispute {
+                platform_config: platform_config_pda(),
+                arbiter: arbiter.pubkey(),
+                dispute,
+                settlement: settlement_pda,
+                held_payment,
+                held_payment_payer: payer,
+                settlement_vault: pda(&[b"settlement-vault", recipient.as_ref()]),
+                refund_token_account: subscriber.wallet_token_account,
+                token_program: spl_token::ID,
+            },
+            instruction::ResolveDispute { refund_amount },
+        )],
+        &[&arbiter],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(test.balance(subscriber.wallet_token_account).await, refund_amount);
+    let dispute: Dispute = test.fetch(dispute).await;
+    assert_eq!(dispute.refunded, refund_amount);
+    test.claim_settlement(&merchant, &[recipient]).await.unwrap();
+    assert_eq!(test.balance(recipient).await, MONTHLY_AMOUNT - refund_amount);
+}
+
+#[tokio::test]
+async fn test_stream_pays_merchant_and_refunds_remainder() {
+    let mut test = Test::new().await;
+    let merchant = test.merchant().await;
+    let subscriber = test.subscriber(&merchant, MONTHLY_AMOUNT).await;
+    let rate = 10;
+    let fee_wallet = test.fee_wallet.pubkey();
+    test.send(
+        &[ix(
+            accounts::InitializeStream {
+                subscription: subscriber.subscription,
+                user: subscriber.user.pubkey(),
+                fee_wallet,
+                escrow_token_account: subscriber.escrow,
+                recipient_token_account: merchant.recipient,
+                system_program: solana_sdk::system_program::ID,
+            },
+            instruction::InitializeStream { rate_per_second: rate, fee_wallet },
+        )],
+        &[&subscriber.user],
+    )
+    .await
+    .unwrap();
+    let started = test.subscription(&subscriber).await.streamed_until;
+
+    test.warp_to(started + 1_000).await;
+    test.send(
+        &[ix(
+            accounts::WithdrawStream {
+                subscription: subscriber.subscription,
+                subscription_signer: subscriber.subscription,
+                escrow_token_account: subscriber.escrow,
+                recipient_token_account: merchant.recipient,
+                merchant: merchant.wallet.pubkey(),
+                token_program: spl_token::ID,
+            },
+            instruction::WithdrawStream {},
+        )],
+        &[&merchant.wallet],
+    )
+    .await
+    .unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, 1_000 * rate);
+
+    // Streams are stopped rather than canceled, so the accrued part isn't
+    // refunded. The page is opened only so the cancel reaches that check.
+    test.schedule_bucket(started).await;
+    let result = test.cancel(&subscriber, CancelMode::Immediate).await;
+    assert_error(result, SubscriptionError::StreamNotBillable);
+
+    test.warp_to(started + 1_500).await;
+    test.send(
+        &[ix(
+            accounts::StopStream {
+                subscription: subscriber.subscription,
+                user: subscriber.user.pubkey(),
+                subscription_signer: subscriber.subscription,
+                escrow_token_account: subscriber.escrow,
+                recipient_token_account: merchant.recipient,
+                destination_token_account: subscriber.wallet_token_account,
+                token_program: spl_token::ID,
+            },
+            instruction::StopStream {},
+        )],
+        &[&subscriber.user],
+    )
+    .await
+    .unwrap();
+    assert_eq!(test.balance(merchant.recipient).await, 1_500 * rate);
+    assert_eq!(test.balance(subscriber.wallet_token_account).await, MONTHLY_AMOUNT - 1_500 * rate);
+    assert!(!test.subscription(&subscriber).await.is_active);
 }
 
+#[tokio::test]
+async fn test_update_fee_wallet() {
+    let mut test = Test::new().await;
+    let new_fee_wallet = Keypair::new();
+    let admin = test.payer();
+    let update = |new_fee_wallet: Pubkey, admin: Pubkey| {
+        ix(
+            accounts::UpdatePlatformFeeWallet { platform_config: platform_config_pda(), admin },
+            instruction::UpdateFeeWallet { new_fee_wallet },
+        )
+    };
+
+    // Only the admin may change it
+    let outsider = Keypair::new();
+    let result = test.send(&[update(new_fee_wallet.pubkey(), outsider.pubkey())], &[&outsider]).await;
+    assert!(result.is_err());
+
+    test.admin_update(update(new_fee_wallet.pubkey(), admin)).await;
+    let platform_config: PlatformConfig = test.fetch(platform_config_pda()).await;
+    assert_eq!(platform_config.fee_wallet, new_fee_wallet.pubkey());
+}